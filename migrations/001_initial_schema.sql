@@ -136,21 +136,3 @@ CREATE TABLE IF NOT EXISTS signup_challenges (
     nonce VARCHAR(64) NOT NULL,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
 );
-
--- ============================================================================
--- MIGRATION TRACKING (for sqlx compatibility)
--- ============================================================================
-
-CREATE TABLE IF NOT EXISTS _sqlx_migrations (
-    version BIGINT PRIMARY KEY,
-    description TEXT NOT NULL,
-    installed_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    success BOOLEAN NOT NULL,
-    checksum BYTEA NOT NULL,
-    execution_time BIGINT NOT NULL
-);
-
--- Record this migration
-INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
-VALUES (1, 'initial_schema', true, E'\\x00', 0)
-ON CONFLICT (version) DO NOTHING;
@@ -0,0 +1,4 @@
+-- Restore the sessions columns dropped by 002 (their old values are not recoverable)
+
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS messages_persisted BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS pending_transaction JSONB;
@@ -0,0 +1,10 @@
+-- Drop the compiler and verification metadata columns
+
+ALTER TABLE contracts DROP COLUMN IF EXISTS compiler_version;
+ALTER TABLE contracts DROP COLUMN IF EXISTS optimization_used;
+ALTER TABLE contracts DROP COLUMN IF EXISTS optimization_runs;
+ALTER TABLE contracts DROP COLUMN IF EXISTS evm_version;
+ALTER TABLE contracts DROP COLUMN IF EXISTS constructor_arguments;
+ALTER TABLE contracts DROP COLUMN IF EXISTS library;
+ALTER TABLE contracts DROP COLUMN IF EXISTS license_type;
+ALTER TABLE contracts DROP COLUMN IF EXISTS swarm_source;
@@ -0,0 +1,10 @@
+-- Compiler and verification metadata reported by Etherscan
+
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS compiler_version TEXT;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS optimization_used BOOLEAN;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS optimization_runs INTEGER;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS evm_version TEXT;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS constructor_arguments TEXT;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS library TEXT;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS license_type TEXT;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS swarm_source TEXT;
@@ -0,0 +1,6 @@
+-- Drop the split source files; contracts.source_code still holds the originals
+
+DROP TABLE IF EXISTS contract_sources;
+
+ALTER TABLE contracts DROP COLUMN IF EXISTS source_format;
+ALTER TABLE contracts DROP COLUMN IF EXISTS compiler_settings;
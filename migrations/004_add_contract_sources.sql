@@ -0,0 +1,13 @@
+-- Individual source files of multi-file and standard JSON contracts
+
+CREATE TABLE IF NOT EXISTS contract_sources (
+    chain_id INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    path TEXT NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY (chain_id, address, path),
+    FOREIGN KEY (chain_id, address) REFERENCES contracts(chain_id, address) ON DELETE CASCADE
+);
+
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS source_format TEXT;
+ALTER TABLE contracts ADD COLUMN IF NOT EXISTS compiler_settings JSONB;
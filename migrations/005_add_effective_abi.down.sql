@@ -0,0 +1,5 @@
+-- Drop the effective ABI functions; proxy and implementation rows are kept
+
+DROP FUNCTION IF EXISTS contract_effective_abi(INTEGER, TEXT);
+DROP FUNCTION IF EXISTS abi_entries(TEXT);
+DROP INDEX IF EXISTS idx_contracts_implementation;
@@ -0,0 +1,44 @@
+-- Effective ABI of a proxy: its own ABI merged with those of the contracts
+-- it delegates to, following implementation_address
+
+CREATE INDEX IF NOT EXISTS idx_contracts_implementation
+    ON contracts(chain_id, implementation_address)
+    WHERE implementation_address IS NOT NULL;
+
+-- ABI entries of a stored `abi` value; unverified contracts hold a message
+-- instead of a JSON array and contribute nothing
+CREATE OR REPLACE FUNCTION abi_entries(abi TEXT) RETURNS JSONB
+LANGUAGE plpgsql IMMUTABLE AS $$
+DECLARE
+    parsed JSONB;
+BEGIN
+    parsed := abi::JSONB;
+    IF jsonb_typeof(parsed) = 'array' THEN
+        RETURN parsed;
+    END IF;
+    RETURN '[]'::JSONB;
+EXCEPTION WHEN others THEN
+    RETURN '[]'::JSONB;
+END;
+$$;
+
+-- Distinct ABI entries of a contract and every implementation behind it,
+-- up to 16 hops; cycles are cut. Empty array for an unknown address.
+CREATE OR REPLACE FUNCTION contract_effective_abi(p_chain_id INTEGER, p_address TEXT)
+RETURNS JSONB
+LANGUAGE sql STABLE AS $$
+    WITH RECURSIVE chain AS (
+        SELECT c.address, c.implementation_address, c.abi, ARRAY[c.address] AS visited
+        FROM contracts c
+        WHERE c.chain_id = p_chain_id AND c.address = lower(p_address)
+        UNION ALL
+        SELECT c.address, c.implementation_address, c.abi, chain.visited || c.address
+        FROM chain
+        JOIN contracts c
+            ON c.chain_id = p_chain_id AND c.address = chain.implementation_address
+        WHERE NOT c.address = ANY(chain.visited)
+            AND cardinality(chain.visited) <= 16
+    )
+    SELECT COALESCE(jsonb_agg(DISTINCT entry), '[]'::JSONB)
+    FROM chain, jsonb_array_elements(abi_entries(chain.abi)) AS entry
+$$;
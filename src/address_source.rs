@@ -0,0 +1,150 @@
+use crate::db_importer::ContractImporter;
+use crate::failures;
+use crate::models::{Address, CuratedAddress};
+use crate::validation;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::fs;
+
+/// Where the addresses of a fetch run come from
+#[async_trait]
+pub trait AddressSource: Send + Sync {
+    /// Human-readable description for progress output
+    fn describe(&self) -> String;
+
+    async fn load(&self) -> Result<Vec<CuratedAddress>>;
+}
+
+/// `address,chain_id,protocol` lines, as in `curated-addresses.txt`
+pub struct CuratedFileSource {
+    pub path: String,
+}
+
+#[async_trait]
+impl AddressSource for CuratedFileSource {
+    fn describe(&self) -> String {
+        format!("curated addresses from {}", self.path)
+    }
+
+    async fn load(&self) -> Result<Vec<CuratedAddress>> {
+        validation::curated_file(&self.path)?.into_rows(&self.path)
+    }
+}
+
+/// The metadata CSV exported from the `contracts` table (`contracts-metadata.csv`)
+pub struct MetadataCsvSource {
+    pub path: String,
+}
+
+#[async_trait]
+impl AddressSource for MetadataCsvSource {
+    fn describe(&self) -> String {
+        format!("metadata CSV {}", self.path)
+    }
+
+    async fn load(&self) -> Result<Vec<CuratedAddress>> {
+        Ok(validation::metadata_csv(&self.path)?
+            .into_rows(&self.path)?
+            .into_iter()
+            .map(|metadata| CuratedAddress {
+                address: metadata.address.clone(),
+                chain_id: metadata.chain_id,
+                protocol: metadata.protocol.clone(),
+                metadata: Some(metadata),
+            })
+            .collect())
+    }
+}
+
+/// A JSON array of `{"address", "chain_id", "protocol"}` objects
+pub struct JsonSource {
+    pub path: String,
+}
+
+#[async_trait]
+impl AddressSource for JsonSource {
+    fn describe(&self) -> String {
+        format!("JSON addresses from {}", self.path)
+    }
+
+    async fn load(&self) -> Result<Vec<CuratedAddress>> {
+        #[derive(Deserialize)]
+        struct JsonAddress {
+            address: Address,
+            chain_id: i32,
+            protocol: Option<String>,
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path))?;
+        let entries: Vec<JsonAddress> = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", self.path))?;
+
+        let targets = entries
+            .into_iter()
+            .map(|entry| CuratedAddress {
+                address: entry.address,
+                chain_id: entry.chain_id,
+                protocol: entry.protocol,
+                metadata: None,
+            })
+            .collect();
+        validation::known_chains(targets, &self.path)
+    }
+}
+
+/// Contracts already stored in the database, e.g. to refresh them
+pub struct DbSource {
+    pub importer: ContractImporter,
+}
+
+#[async_trait]
+impl AddressSource for DbSource {
+    fn describe(&self) -> String {
+        "contracts table".to_string()
+    }
+
+    async fn load(&self) -> Result<Vec<CuratedAddress>> {
+        let rows: Vec<(String, i32, Option<String>)> = sqlx::query_as(
+            "SELECT address, chain_id, protocol FROM contracts ORDER BY chain_id, address",
+        )
+        .fetch_all(self.importer.pool())
+        .await?;
+
+        let targets = rows
+            .into_iter()
+            .map(|(address, chain_id, protocol)| {
+                Ok(CuratedAddress {
+                    address: Address::parse(&address).map_err(anyhow::Error::msg)?,
+                    chain_id,
+                    protocol,
+                    metadata: None,
+                })
+            })
+            .collect::<Result<_>>()?;
+        validation::known_chains(targets, "the contracts table")
+    }
+}
+
+/// A `--failures-out` report, optionally limited to some error kinds
+pub struct FailureReportSource {
+    pub path: String,
+    pub kinds: Vec<String>,
+}
+
+#[async_trait]
+impl AddressSource for FailureReportSource {
+    fn describe(&self) -> String {
+        format!("failure report {}", self.path)
+    }
+
+    async fn load(&self) -> Result<Vec<CuratedAddress>> {
+        let targets = failures::read_failures(&self.path)?
+            .into_iter()
+            .filter(|record| self.kinds.is_empty() || self.kinds.contains(&record.error_kind))
+            .map(failures::FailureRecord::into_target)
+            .collect();
+        validation::known_chains(targets, &self.path)
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::OnceLock;
+
+/// The chain list shipped with the tool
+const BUNDLED_CHAINS: &str = include_str!("../chains.json");
+
+/// One entry of the chain registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chain {
+    pub chain_id: i32,
+    /// Canonical name, written to the `chain` column of `contracts`
+    pub name: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    pub native_symbol: String,
+    /// Etherscan-compatible API endpoint used to fetch sources
+    pub explorer_api_url: String,
+    pub block_time_secs: f64,
+}
+
+/// Known chains by id
+#[derive(Debug)]
+pub struct ChainRegistry {
+    chains: BTreeMap<i32, Chain>,
+}
+
+impl ChainRegistry {
+    /// The bundled `chains.json`, with entries of `override_path` replacing or
+    /// adding chains by `chain_id`
+    pub fn load(override_path: Option<&str>) -> Result<Self> {
+        let mut registry = Self::parse(BUNDLED_CHAINS).context("Invalid bundled chains.json")?;
+
+        if let Some(path) = override_path {
+            let content =
+                fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+            let overrides = Self::parse(&content)
+                .with_context(|| format!("Failed to parse chain registry {}", path))?;
+            registry.chains.extend(overrides.chains);
+        }
+
+        Ok(registry)
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let chains: Vec<Chain> = serde_json::from_str(content)?;
+        Ok(Self {
+            chains: chains
+                .into_iter()
+                .map(|chain| (chain.chain_id, chain))
+                .collect(),
+        })
+    }
+
+    pub fn get(&self, chain_id: i32) -> Option<&Chain> {
+        self.chains.get(&chain_id)
+    }
+
+    /// Canonical name of a chain, or `chain_{id}` if it isn't registered
+    pub fn name(&self, chain_id: i32) -> String {
+        self.get(chain_id)
+            .map(|chain| chain.name.clone())
+            .unwrap_or_else(|| format!("chain_{}", chain_id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Chain> {
+        self.chains.values()
+    }
+}
+
+static REGISTRY: OnceLock<ChainRegistry> = OnceLock::new();
+
+/// Install the registry used by the rest of the process; call once at startup
+pub fn init(registry: ChainRegistry) {
+    REGISTRY
+        .set(registry)
+        .expect("chain registry initialized twice");
+}
+
+/// The registry installed by [`init`], or the bundled one
+pub fn registry() -> &'static ChainRegistry {
+    REGISTRY.get_or_init(|| ChainRegistry::load(None).expect("bundled chains.json must be valid"))
+}
@@ -0,0 +1,134 @@
+use crate::models::CuratedAddress;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Save after this many recorded outcomes, plus once at the end of a run
+const SAVE_EVERY: usize = 25;
+
+/// `(chain_id, lowercase address)` identifying a contract across runs
+pub type ContractKey = (i32, String);
+
+pub fn contract_key(chain_id: i32, address: &str) -> ContractKey {
+    (chain_id, address.to_lowercase())
+}
+
+/// Drop targets whose key is in `done`, reporting how many were skipped
+pub fn skip_completed(
+    addresses: Vec<CuratedAddress>,
+    done: &HashSet<ContractKey>,
+) -> Vec<CuratedAddress> {
+    let before = addresses.len();
+    let remaining: Vec<CuratedAddress> = addresses
+        .into_iter()
+        .filter(|addr| !done.contains(&contract_key(addr.chain_id, addr.address.as_str())))
+        .collect();
+
+    println!(
+        "⏭️  Resuming: skipping {} already fetched addresses",
+        before - remaining.len()
+    );
+    remaining
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointState {
+    completed: BTreeSet<String>,
+    /// Key -> last error message
+    failed: BTreeMap<String, String>,
+    pending: BTreeSet<String>,
+}
+
+/// Progress of a fetch run, persisted as JSON so an interrupted run can resume
+pub struct Checkpoint {
+    path: PathBuf,
+    state: CheckpointState,
+    unsaved: usize,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint at `path`, continuing the existing one if `resume` is set
+    pub fn open(path: impl Into<PathBuf>, resume: bool) -> Result<Self> {
+        let path = path.into();
+
+        let state = if resume && path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read checkpoint {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse checkpoint {}", path.display()))?
+        } else {
+            CheckpointState::default()
+        };
+
+        Ok(Self {
+            path,
+            state,
+            unsaved: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn completed_keys(&self) -> HashSet<ContractKey> {
+        self.state
+            .completed
+            .iter()
+            .filter_map(|key| parse_key(key))
+            .collect()
+    }
+
+    /// Mark the targets of this run as pending
+    pub fn set_pending(&mut self, addresses: &[CuratedAddress]) {
+        self.state.pending = addresses
+            .iter()
+            .map(|addr| format_key(addr.chain_id, addr.address.as_str()))
+            .collect();
+    }
+
+    pub fn record_completed(&mut self, chain_id: i32, address: &str) -> Result<()> {
+        let key = format_key(chain_id, address);
+        self.state.pending.remove(&key);
+        self.state.failed.remove(&key);
+        self.state.completed.insert(key);
+        self.mark_dirty()
+    }
+
+    pub fn record_failed(&mut self, chain_id: i32, address: &str, error: &str) -> Result<()> {
+        let key = format_key(chain_id, address);
+        self.state.pending.remove(&key);
+        self.state.failed.insert(key, error.to_string());
+        self.mark_dirty()
+    }
+
+    fn mark_dirty(&mut self) -> Result<()> {
+        self.unsaved += 1;
+        if self.unsaved >= SAVE_EVERY {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Write the checkpoint atomically via a temporary file
+    pub fn save(&mut self) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&self.state)?)
+            .with_context(|| format!("Failed to write checkpoint {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write checkpoint {}", self.path.display()))?;
+        self.unsaved = 0;
+        Ok(())
+    }
+}
+
+fn format_key(chain_id: i32, address: &str) -> String {
+    format!("{}:{}", chain_id, address.to_lowercase())
+}
+
+fn parse_key(key: &str) -> Option<ContractKey> {
+    let (chain_id, address) = key.split_once(':')?;
+    Some((chain_id.parse().ok()?, address.to_string()))
+}
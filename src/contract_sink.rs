@@ -0,0 +1,241 @@
+use crate::checkpoint::{contract_key, ContractKey};
+use crate::csv_handler;
+use crate::db_importer::ContractImporter;
+use crate::models::ContractData;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Where fetched contracts are written
+#[async_trait]
+pub trait ContractSink: Send {
+    /// Human-readable description for progress output
+    fn describe(&self) -> String;
+
+    /// Contracts the sink wants per write; 1 streams every row as it arrives
+    fn batch_size(&self) -> usize {
+        1
+    }
+
+    /// Contracts already present in the sink, used by `--resume`
+    async fn existing_keys(&self) -> Result<HashSet<ContractKey>>;
+
+    /// Persist a batch, returning how many contracts were written
+    async fn write_batch(&mut self, contracts: &[ContractData]) -> Result<usize>;
+
+    async fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn open_output(path: &str, append: bool) -> Result<File> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(append)
+        .write(!append)
+        .truncate(!append)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path))?;
+
+    // Don't glue the first new line onto an unterminated last one
+    if append && file.metadata()?.len() > 0 && !csv_handler::ends_with_newline(path)? {
+        file.write_all(b"\n")?;
+    }
+    Ok(file)
+}
+
+/// Full `ContractData` rows in a CSV file
+pub struct CsvSink {
+    path: String,
+    append: bool,
+    writer: Option<csv::Writer<File>>,
+}
+
+impl CsvSink {
+    /// With `append` set, rows are added to an existing file without
+    /// repeating the header; otherwise the file is replaced on first write.
+    /// Appending to a CSV whose header doesn't match `ContractData` fails.
+    pub fn new(path: String, append: bool) -> Self {
+        Self {
+            path,
+            append,
+            writer: None,
+        }
+    }
+
+    fn writer(&mut self) -> Result<&mut csv::Writer<File>> {
+        if self.writer.is_none() {
+            self.writer = Some(csv_handler::contract_csv_writer(&self.path, self.append)?);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+}
+
+#[async_trait]
+impl ContractSink for CsvSink {
+    fn describe(&self) -> String {
+        format!("CSV {}", self.path)
+    }
+
+    async fn existing_keys(&self) -> Result<HashSet<ContractKey>> {
+        if !Path::new(&self.path).exists() {
+            return Ok(HashSet::new());
+        }
+        csv_handler::read_contract_keys(&self.path)
+    }
+
+    async fn write_batch(&mut self, contracts: &[ContractData]) -> Result<usize> {
+        let writer = self.writer()?;
+        for contract in contracts {
+            writer.serialize(contract)?;
+        }
+        writer.flush()?;
+        Ok(contracts.len())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        self.writer()?.flush()?;
+        Ok(())
+    }
+}
+
+/// One JSON object per line
+pub struct JsonlSink {
+    path: String,
+    append: bool,
+    writer: Option<BufWriter<File>>,
+}
+
+impl JsonlSink {
+    pub fn new(path: String, append: bool) -> Self {
+        Self {
+            path,
+            append,
+            writer: None,
+        }
+    }
+
+    fn writer(&mut self) -> Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            let file = open_output(&self.path, self.append)?;
+            self.writer = Some(BufWriter::new(file));
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+}
+
+#[async_trait]
+impl ContractSink for JsonlSink {
+    fn describe(&self) -> String {
+        format!("JSONL {}", self.path)
+    }
+
+    async fn existing_keys(&self) -> Result<HashSet<ContractKey>> {
+        #[derive(Deserialize)]
+        struct KeyRow {
+            address: String,
+            chain_id: i32,
+        }
+
+        let mut keys = HashSet::new();
+        let Ok(file) = File::open(&self.path) else {
+            return Ok(keys);
+        };
+
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        let (mut offset, mut line_number) = (0u64, 0u64);
+        // A line that failed to parse, with where it starts; only tolerated
+        // as the last one, as left by a run killed mid-write
+        let mut partial: Option<(anyhow::Error, u64, u64)> = None;
+
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            let start = offset;
+            offset += read as u64;
+            line_number += 1;
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            if let Some((e, ..)) = partial.take() {
+                return Err(e);
+            }
+
+            match serde_json::from_slice::<KeyRow>(&line) {
+                Ok(row) => {
+                    keys.insert(contract_key(row.chain_id, &row.address));
+                }
+                Err(e) => {
+                    let e = anyhow::Error::new(e).context(format!(
+                        "Failed to parse line {} of {}",
+                        line_number, self.path
+                    ));
+                    partial = Some((e, start, line_number));
+                }
+            }
+        }
+
+        if let Some((_, start, line_number)) = partial {
+            csv_handler::drop_partial_record(&self.path, start, line_number)?;
+        }
+
+        Ok(keys)
+    }
+
+    async fn write_batch(&mut self, contracts: &[ContractData]) -> Result<usize> {
+        let writer = self.writer()?;
+        for contract in contracts {
+            serde_json::to_writer(&mut *writer, contract)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(contracts.len())
+    }
+
+    async fn finish(&mut self) -> Result<()> {
+        self.writer()?.flush()?;
+        Ok(())
+    }
+}
+
+/// The `contracts` table
+pub struct PostgresSink {
+    importer: ContractImporter,
+    batch_size: usize,
+}
+
+impl PostgresSink {
+    pub fn new(importer: ContractImporter, batch_size: usize) -> Self {
+        Self {
+            importer,
+            batch_size,
+        }
+    }
+}
+
+#[async_trait]
+impl ContractSink for PostgresSink {
+    fn describe(&self) -> String {
+        "database".to_string()
+    }
+
+    fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    async fn existing_keys(&self) -> Result<HashSet<ContractKey>> {
+        self.importer.existing_contract_keys().await
+    }
+
+    async fn write_batch(&mut self, contracts: &[ContractData]) -> Result<usize> {
+        Ok(self.importer.import(contracts).await?.total() as usize)
+    }
+}
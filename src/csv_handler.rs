@@ -1,59 +1,275 @@
+use crate::checkpoint::{contract_key, ContractKey};
 use crate::models::ContractData;
-use anyhow::Result;
-use csv::{Reader, Writer};
-use std::fs::File;
-use std::path::Path;
+use anyhow::{bail, Context, Result};
+use csv::{Reader, Writer, WriterBuilder};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
 
-pub fn write_contracts_to_csv(contracts: &[ContractData], output_path: &str) -> Result<()> {
-    let mut writer = Writer::from_path(output_path)?;
-
-    for contract in contracts {
-        writer.serialize(contract)?;
-    }
+/// Stream the contracts of a CSV one row at a time, so sources and ABIs are
+/// never all held in memory
+pub fn read_contracts_from_csv(
+    input_path: &str,
+) -> Result<impl Iterator<Item = Result<ContractData>>> {
+    let reader = Reader::from_path(input_path)?;
+    Ok(reader
+        .into_deserialize()
+        .map(|result| result.map_err(Into::into)))
+}
 
-    writer.flush()?;
-    Ok(())
+/// Take up to `size` contracts from the stream; empty once it is exhausted
+pub fn next_batch(
+    contracts: &mut impl Iterator<Item = Result<ContractData>>,
+    size: usize,
+) -> Result<Vec<ContractData>> {
+    contracts.take(size.max(1)).collect()
 }
 
-pub fn read_contracts_from_csv(input_path: &str) -> Result<Vec<ContractData>> {
-    let mut contracts = Vec::new();
+/// Read only the `(chain_id, address)` keys of a contracts CSV. A malformed
+/// last row, as left by a run killed mid-write, is cut off with a warning.
+pub fn read_contract_keys(input_path: &str) -> Result<HashSet<ContractKey>> {
+    #[derive(Deserialize)]
+    struct KeyRow {
+        address: String,
+        chain_id: i32,
+    }
+
+    let mut keys = HashSet::new();
     let mut reader = Reader::from_path(input_path)?;
+    let headers = reader.headers()?.clone();
+    // A row that failed to parse, which is only tolerated as the last one
+    let mut partial: Option<csv::Error> = None;
 
-    for result in reader.deserialize() {
-        let contract: ContractData = result?;
-        contracts.push(contract);
+    for result in reader.records() {
+        if let Some(e) = partial.take() {
+            return Err(e).with_context(|| format!("Failed to read {}", input_path));
+        }
+        match result.and_then(|record| record.deserialize::<KeyRow>(Some(&headers))) {
+            Ok(row) => {
+                keys.insert(contract_key(row.chain_id, &row.address));
+            }
+            Err(e) => partial = Some(e),
+        }
     }
+    drop(reader);
 
-    Ok(contracts)
-}
+    if let Some(e) = partial {
+        let Some(position) = e.position().cloned() else {
+            return Err(e).with_context(|| format!("Failed to read {}", input_path));
+        };
+        drop_partial_record(input_path, position.byte(), position.line())?;
+    }
 
-pub fn append_contract_to_csv(contract: &ContractData, output_path: &str) -> Result<()> {
-    let file_exists = Path::new(output_path).exists();
+    Ok(keys)
+}
 
-    let file = std::fs::OpenOptions::new()
+/// Cut the file at `offset`, where a partial last record starts, so a resumed
+/// run appends after the last complete one
+pub fn drop_partial_record(path: &str, offset: u64, line: u64) -> Result<()> {
+    println!(
+        "⚠️  {} ends with a partial record at line {} (interrupted run?); dropping it",
+        path, line
+    );
+    OpenOptions::new()
         .write(true)
+        .open(path)
+        .and_then(|file| file.set_len(offset))
+        .with_context(|| format!("Failed to truncate {}", path))
+}
+
+/// Columns of a contracts CSV, in `ContractData` field order
+pub const CONTRACT_COLUMNS: &[&str] = &[
+    "address",
+    "chain",
+    "chain_id",
+    "name",
+    "symbol",
+    "source_code",
+    "abi",
+    "is_proxy",
+    "implementation_address",
+    "protocol",
+    "contract_type",
+    "version",
+    "updated_at",
+    "compiler_version",
+    "optimization_used",
+    "optimization_runs",
+    "evm_version",
+    "constructor_arguments",
+    "library",
+    "license_type",
+    "swarm_source",
+];
+
+/// Open a contracts CSV for writing. With `append`, rows go after the existing
+/// ones and the header is only written to a new or empty file, whose header
+/// must then match `ContractData`; otherwise the file is replaced.
+pub fn contract_csv_writer(output_path: &str, append: bool) -> Result<Writer<File>> {
+    let has_rows = append
+        && std::fs::metadata(output_path)
+            .map(|m| m.len() > 0)
+            .unwrap_or(false);
+
+    if has_rows {
+        check_contract_header(output_path)?;
+    }
+
+    let mut file = OpenOptions::new()
         .create(true)
-        .append(true)
-        .open(output_path)?;
+        .append(has_rows)
+        .write(!has_rows)
+        .truncate(!has_rows)
+        .open(output_path)
+        .with_context(|| format!("Failed to open {}", output_path))?;
 
-    let mut writer = Writer::from_writer(file);
+    // Don't glue the first new row onto an unterminated last line
+    if has_rows && !ends_with_newline(output_path)? {
+        file.write_all(b"\n")?;
+    }
 
-    // Write header if file is new
-    if !file_exists {
-        writer.serialize(contract)?;
-    } else {
-        // Skip header for existing files
-        writer.serialize(contract)?;
+    // Write the header up front so even a run that fetches nothing leaves a valid CSV
+    let mut writer = WriterBuilder::new().has_headers(false).from_writer(file);
+    if !has_rows {
+        writer.write_record(CONTRACT_COLUMNS)?;
     }
+    Ok(writer)
+}
 
-    writer.flush()?;
+fn check_contract_header(path: &str) -> Result<()> {
+    let mut reader = Reader::from_path(path)?;
+    let header: Vec<&str> = reader.headers()?.iter().collect();
+    if header != CONTRACT_COLUMNS {
+        bail!(
+            "Cannot append to {}: its header is {:?}, expected {:?}",
+            path,
+            header,
+            CONTRACT_COLUMNS
+        );
+    }
     Ok(())
 }
 
-pub fn csv_exists_with_data(path: &str) -> bool {
-    if let Ok(file) = File::open(path) {
-        let mut reader = Reader::from_reader(file);
-        return reader.records().count() > 0;
+pub fn ends_with_newline(path: &str) -> Result<bool> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0u8];
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Address;
+
+    fn contract(hex_digit: char) -> ContractData {
+        ContractData {
+            address: Address::parse(&format!("0x{}", hex_digit.to_string().repeat(40))).unwrap(),
+            chain: "ethereum".to_string(),
+            chain_id: 1,
+            name: "Token".to_string(),
+            symbol: None,
+            source_code: "contract Token {\n}".to_string(),
+            abi: "[]".to_string(),
+            is_proxy: false,
+            implementation_address: None,
+            protocol: None,
+            contract_type: None,
+            version: None,
+            updated_at: Some(1_700_000_000),
+            compiler_version: None,
+            optimization_used: None,
+            optimization_runs: None,
+            evm_version: None,
+            constructor_arguments: None,
+            library: None,
+            license_type: None,
+            swarm_source: None,
+        }
+    }
+
+    fn write(path: &str, append: bool, contracts: &[ContractData]) {
+        let mut writer = contract_csv_writer(path, append).unwrap();
+        for contract in contracts {
+            writer.serialize(contract).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    #[test]
+    fn columns_match_contract_data_fields() {
+        let mut writer = Writer::from_writer(Vec::new());
+        writer.serialize(contract('1')).unwrap();
+        let written = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+
+        let header = written.lines().next().unwrap();
+        assert_eq!(header, CONTRACT_COLUMNS.join(","));
+    }
+
+    #[test]
+    fn append_writes_one_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contracts.csv");
+        let path = path.to_str().unwrap();
+
+        write(path, true, &[contract('1')]);
+        write(path, true, &[contract('2')]);
+
+        let content = std::fs::read_to_string(path).unwrap();
+        assert_eq!(content.matches("address,chain,chain_id").count(), 1);
+        let addresses: Vec<String> = read_contracts_from_csv(path)
+            .unwrap()
+            .map(|contract| contract.unwrap().address.to_string())
+            .collect();
+        assert_eq!(
+            addresses,
+            [
+                "0x1111111111111111111111111111111111111111",
+                "0x2222222222222222222222222222222222222222"
+            ]
+        );
+    }
+
+    #[test]
+    fn append_after_unterminated_last_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contracts.csv");
+        let path = path.to_str().unwrap();
+
+        write(path, false, &[contract('1')]);
+        let content = std::fs::read_to_string(path).unwrap();
+        std::fs::write(path, content.trim_end_matches('\n')).unwrap();
+        write(path, true, &[contract('2')]);
+
+        assert_eq!(read_contracts_from_csv(path).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn append_rejects_a_different_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contracts.csv");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "address,chain_id,protocol\n0x1,1,aave\n").unwrap();
+
+        let err = contract_csv_writer(path, true).unwrap_err();
+        assert!(err.to_string().contains("Cannot append"), "{}", err);
+        // The file is left as it was
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "address,chain_id,protocol\n0x1,1,aave\n"
+        );
+    }
+
+    #[test]
+    fn without_append_the_file_is_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contracts.csv");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "address,chain_id,protocol\n").unwrap();
+
+        write(path, false, &[contract('1')]);
+        assert_eq!(read_contracts_from_csv(path).unwrap().count(), 1);
     }
-    false
 }
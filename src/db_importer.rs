@@ -1,68 +1,1049 @@
-use crate::models::ContractData;
-use anyhow::Result;
-use sqlx::postgres::PgPool;
+use crate::chains;
+use crate::checkpoint::{contract_key, ContractKey};
+use crate::csv_handler;
+use crate::merge_preview::{
+    ColumnChange, ContractChange, ContractRef, MergePreview, PREVIEW_VALUE_CHARS,
+};
+use crate::models::{Address, ContractData};
+use crate::source_files::parse_sources;
+use crate::validation;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use sqlx::{Executor, PgConnection, Row};
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::time::sleep;
 
-pub async fn import_contracts_to_db(
+/// Connection settings for [`ContractImporter`]
+#[derive(Debug, Clone)]
+pub struct ImporterOptions {
+    pub max_connections: u32,
+    /// Server-side `statement_timeout` for every pooled connection
+    pub statement_timeout: Option<Duration>,
+    /// Attempts per connection or statement before a connection error is fatal
+    pub max_attempts: u32,
+}
+
+impl Default for ImporterOptions {
+    fn default() -> Self {
+        Self {
+            max_connections: 5,
+            statement_timeout: Some(Duration::from_secs(60)),
+            max_attempts: 5,
+        }
+    }
+}
+
+/// Imports contracts over one long-lived connection pool
+#[derive(Clone)]
+pub struct ContractImporter {
+    pool: PgPool,
+    max_attempts: u32,
+    policy: MergePolicy,
+}
+
+impl ContractImporter {
+    pub async fn connect(database_url: &str, options: ImporterOptions) -> Result<Self> {
+        let mut connect_options =
+            PgConnectOptions::from_str(database_url).context("Invalid database URL")?;
+        if let Some(timeout) = options.statement_timeout {
+            connect_options = connect_options
+                .options([("statement_timeout", format!("{}ms", timeout.as_millis()))]);
+        }
+
+        let pool_options = PgPoolOptions::new().max_connections(options.max_connections.max(1));
+        let max_attempts = options.max_attempts.max(1);
+        let mut attempt = 1;
+
+        let pool = loop {
+            match pool_options
+                .clone()
+                .connect_with(connect_options.clone())
+                .await
+            {
+                Ok(pool) => break pool,
+                Err(e) if is_connection_error(&e) && attempt < max_attempts => {
+                    eprintln!(
+                        "⚠️  Database connection failed (attempt {}/{}): {}",
+                        attempt, max_attempts, e
+                    );
+                    sleep(retry_delay(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e).context("Failed to connect to database"),
+            }
+        };
+
+        Ok(Self {
+            pool,
+            max_attempts,
+            policy: MergePolicy::default(),
+        })
+    }
+
+    pub fn with_merge_policy(mut self, policy: MergePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    /// Merge `contracts` into the table in one transaction, so a batch is
+    /// either fully imported or not at all
+    pub async fn import(&self, contracts: &[ContractData]) -> Result<MergeSummary> {
+        if contracts.is_empty() {
+            return Ok(MergeSummary::default());
+        }
+
+        self.with_retry(|| self.import_batch(contracts))
+            .await
+            .with_context(|| format!("Failed to import a batch of {} contracts", contracts.len()))
+    }
+
+    async fn import_batch(&self, contracts: &[ContractData]) -> Result<MergeSummary, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        tx.execute(CREATE_STAGING_TABLE).await?;
+        stage_contracts(&mut tx, contracts).await?;
+
+        let unsplit = unsplit_sources(&mut tx).await?;
+        let summary = merge_staged(&mut tx, &self.policy).await?;
+        store_sources(&mut tx, &unsplit).await?;
+        tx.commit().await?;
+
+        Ok(summary)
+    }
+
+    /// What `import` would do with `contracts`, without writing anything
+    pub async fn preview_import(&self, csv_path: &str, batch_size: usize) -> Result<MergePreview> {
+        self.with_retry(|| async {
+            let mut tx = self.pool.begin().await?;
+            tx.execute(CREATE_STAGING_TABLE).await?;
+
+            // Re-read the CSV on every attempt rather than holding it in memory
+            let mut contracts = csv_handler::read_contracts_from_csv(csv_path)
+                .map_err(|e| sqlx::Error::Decode(e.into()))?;
+            loop {
+                let batch = csv_handler::next_batch(&mut contracts, batch_size)
+                    .map_err(|e| sqlx::Error::Decode(e.into()))?;
+                if batch.is_empty() {
+                    break;
+                }
+                stage_contracts(&mut tx, &batch).await?;
+            }
+
+            let preview = preview_staged(&mut tx, &self.policy).await?;
+            tx.rollback().await?;
+
+            Ok(preview)
+        })
+        .await
+        .context("Failed to preview the import")
+    }
+
+    /// Load a contracts CSV with `COPY` into a temporary staging table and merge
+    /// it into `contracts`, all in one transaction
+    pub async fn seed(&self, csv_path: &str) -> Result<MergeSummary> {
+        validation::contracts_csv_addresses(csv_path)?.into_rows(csv_path)?;
+        let copy = copy_statement(csv_path)?;
+
+        self.with_retry(|| async {
+            let mut tx = self.pool.begin().await?;
+            stage_csv(&mut tx, &copy, csv_path).await?;
+
+            let unsplit = unsplit_sources(&mut tx).await?;
+            let summary = merge_staged(&mut tx, &self.policy).await?;
+            store_sources(&mut tx, &unsplit).await?;
+            tx.commit().await?;
+
+            Ok(summary)
+        })
+        .await
+        .with_context(|| format!("Failed to seed from {}", csv_path))
+    }
+
+    /// What `seed` would do with a contracts CSV, without writing anything
+    pub async fn preview_seed(&self, csv_path: &str) -> Result<MergePreview> {
+        validation::contracts_csv_addresses(csv_path)?.into_rows(csv_path)?;
+        let copy = copy_statement(csv_path)?;
+
+        self.with_retry(|| async {
+            let mut tx = self.pool.begin().await?;
+            stage_csv(&mut tx, &copy, csv_path).await?;
+
+            let preview = preview_staged(&mut tx, &self.policy).await?;
+            tx.rollback().await?;
+
+            Ok(preview)
+        })
+        .await
+        .with_context(|| format!("Failed to preview seeding from {}", csv_path))
+    }
+
+    pub async fn count_contracts(&self) -> Result<i64> {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM contracts")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    /// Retry connection-level failures; anything else is returned as is
+    async fn with_retry<T, F, Fut>(&self, mut operation: F) -> Result<T, sqlx::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            match operation().await {
+                Err(e) if is_connection_error(&e) && attempt < self.max_attempts => {
+                    eprintln!(
+                        "⚠️  Database connection lost (attempt {}/{}): {}",
+                        attempt, self.max_attempts, e
+                    );
+                    sleep(retry_delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Keys of every contract already stored in the database
+    pub async fn existing_contract_keys(&self) -> Result<HashSet<ContractKey>> {
+        let rows: Vec<(i32, String)> = sqlx::query_as("SELECT chain_id, address FROM contracts")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(chain_id, address)| contract_key(chain_id, &address))
+            .collect())
+    }
+}
+
+/// Append `contracts` to the staging table with one UNNEST statement
+async fn stage_contracts(
+    tx: &mut PgConnection,
     contracts: &[ContractData],
-    database_url: &str,
-) -> Result<usize> {
-    let pool = PgPool::connect(database_url).await?;
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO _contracts_staging (
+            address, chain, chain_id, source_code, abi, name, symbol,
+            is_proxy, implementation_address, protocol, contract_type, version,
+            updated_at, compiler_version, optimization_used, optimization_runs,
+            evm_version, constructor_arguments, library, license_type, swarm_source
+        )
+        SELECT * FROM UNNEST(
+            $1::TEXT[], $2::TEXT[], $3::INTEGER[], $4::TEXT[], $5::TEXT[], $6::TEXT[],
+            $7::TEXT[], $8::TEXT[], $9::TEXT[], $10::TEXT[], $11::TEXT[], $12::TEXT[],
+            $13::BIGINT[], $14::TEXT[], $15::TEXT[], $16::INTEGER[], $17::TEXT[],
+            $18::TEXT[], $19::TEXT[], $20::TEXT[], $21::TEXT[]
+        )
+        "#,
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.address.as_str())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.chain.as_str())
+            .collect::<Vec<_>>(),
+    )
+    .bind(contracts.iter().map(|c| c.chain_id).collect::<Vec<_>>())
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.source_code.as_str())
+            .collect::<Vec<_>>(),
+    )
+    .bind(contracts.iter().map(|c| c.abi.as_str()).collect::<Vec<_>>())
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.symbol.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.is_proxy.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.implementation_address.as_ref().map(Address::as_str))
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.protocol.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.contract_type.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.version.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(contracts.iter().map(|c| c.updated_at).collect::<Vec<_>>())
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.compiler_version.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.optimization_used.map(|used| used.to_string()))
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.optimization_runs)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.evm_version.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.constructor_arguments.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.library.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.license_type.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        contracts
+            .iter()
+            .map(|c| c.swarm_source.as_deref())
+            .collect::<Vec<_>>(),
+    )
+    .execute(&mut *tx)
+    .await?;
 
-    let mut imported = 0;
+    Ok(())
+}
 
-    for contract in contracts {
-        let now = chrono::Utc::now().timestamp();
+/// Staged contracts whose source is new, changed or not split into files yet
+async fn unsplit_sources(tx: &mut PgConnection) -> Result<HashSet<ContractKey>, sqlx::Error> {
+    let rows: Vec<(i32, String)> = sqlx::query_as(&format!(
+        r#"
+        SELECT s.chain_id, s.address
+        FROM {} LEFT JOIN contracts c ON c.chain_id = s.chain_id AND c.address = s.address
+        WHERE c.source_format IS NULL OR c.source_code IS DISTINCT FROM s.source_code
+        "#,
+        STAGED
+    ))
+    .fetch_all(&mut *tx)
+    .await?;
 
-        let result = sqlx::query(
+    Ok(rows
+        .into_iter()
+        .map(|(chain_id, address)| contract_key(chain_id, &address))
+        .collect())
+}
+
+/// Contracts whose sources are read back and split per round trip, which
+/// bounds memory on large seeds
+const SOURCE_CHUNK: usize = 200;
+
+/// Split the staged sources of the `unsplit` contracts into `contract_sources`,
+/// for those whose stored source is now the staged one (the merge policy may
+/// have kept the old source)
+async fn store_sources(
+    tx: &mut PgConnection,
+    unsplit: &HashSet<ContractKey>,
+) -> Result<(), sqlx::Error> {
+    let keys: Vec<&ContractKey> = unsplit.iter().collect();
+    for chunk in keys.chunks(SOURCE_CHUNK) {
+        let chain_ids: Vec<i32> = chunk.iter().map(|key| key.0).collect();
+        let addresses: Vec<&str> = chunk.iter().map(|key| key.1.as_str()).collect();
+
+        // Seeded rows may leave out the name and compiler; use the stored ones
+        let rows: Vec<(i32, String, String, String, Option<String>)> = sqlx::query_as(&format!(
             r#"
-            INSERT INTO contracts (
-                address, chain, chain_id, source_code, abi, name, symbol,
-                is_proxy, implementation_address, protocol, contract_type, version,
-                created_at, updated_at
-            )
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
-            ON CONFLICT (chain_id, address) DO UPDATE SET
-                source_code = EXCLUDED.source_code,
-                abi = EXCLUDED.abi,
-                name = EXCLUDED.name,
-                symbol = EXCLUDED.symbol,
-                is_proxy = EXCLUDED.is_proxy,
-                implementation_address = EXCLUDED.implementation_address,
-                protocol = EXCLUDED.protocol,
-                contract_type = EXCLUDED.contract_type,
-                version = EXCLUDED.version,
-                updated_at = EXCLUDED.updated_at
+            SELECT s.chain_id, s.address, COALESCE(NULLIF(s.name, ''), c.name, 'Unknown'),
+                s.source_code, COALESCE(s.compiler_version, c.compiler_version)
+            FROM {}
+            JOIN UNNEST($1::INTEGER[], $2::TEXT[]) AS u(chain_id, address)
+                ON s.chain_id = u.chain_id AND s.address = u.address
+            LEFT JOIN contracts c ON c.chain_id = s.chain_id AND c.address = s.address
+            WHERE s.source_code IS NOT NULL
             "#,
+            STAGED
+        ))
+        .bind(&chain_ids)
+        .bind(&addresses)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        store_split_sources(tx, &rows).await?;
+    }
+
+    Ok(())
+}
+
+/// Write the split files, format and settings of `(chain_id, address, name,
+/// source_code, compiler_version)` rows
+async fn store_split_sources(
+    tx: &mut PgConnection,
+    rows: &[(i32, String, String, String, Option<String>)],
+) -> Result<(), sqlx::Error> {
+    let mut parsed = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let mut files = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for (chain_id, address, name, source_code, compiler_version) in rows {
+        let Some(sources) = parse_sources(source_code, name, compiler_version.as_deref()) else {
+            continue;
+        };
+
+        parsed.0.push(*chain_id);
+        parsed.1.push(address.as_str());
+        parsed.2.push(sources.format.as_str());
+        parsed
+            .3
+            .push(sources.settings.map(|settings| settings.to_string()));
+        for file in sources.files {
+            files.0.push(*chain_id);
+            files.1.push(address.as_str());
+            files.2.push(file.path);
+            files.3.push(file.content);
+        }
+    }
+    if parsed.0.is_empty() {
+        return Ok(());
+    }
+
+    let current = format!(
+        "{} JOIN contracts c ON c.chain_id = s.chain_id AND c.address = s.address \
+         AND c.source_code = s.source_code",
+        STAGED
+    );
+
+    sqlx::query(&format!(
+        r#"
+        DELETE FROM contract_sources cs
+        USING UNNEST($1::INTEGER[], $2::TEXT[]) AS u(chain_id, address), {}
+        WHERE cs.chain_id = u.chain_id AND cs.address = u.address
+          AND s.chain_id = u.chain_id AND s.address = u.address
+        "#,
+        current
+    ))
+    .bind(&parsed.0)
+    .bind(&parsed.1)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO contract_sources (chain_id, address, path, content)
+        SELECT f.chain_id, f.address, f.path, f.content
+        FROM UNNEST($1::INTEGER[], $2::TEXT[], $3::TEXT[], $4::TEXT[])
+            AS f(chain_id, address, path, content)
+        JOIN {} ON s.chain_id = f.chain_id AND s.address = f.address
+        ON CONFLICT (chain_id, address, path) DO UPDATE SET content = EXCLUDED.content
+        "#,
+        current
+    ))
+    .bind(&files.0)
+    .bind(&files.1)
+    .bind(&files.2)
+    .bind(&files.3)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(&format!(
+        r#"
+        UPDATE contracts c SET
+            source_format = u.format,
+            compiler_settings = u.settings::JSONB
+        FROM UNNEST($1::INTEGER[], $2::TEXT[], $3::TEXT[], $4::TEXT[])
+            AS u(chain_id, address, format, settings),
+            {}
+        WHERE c.chain_id = u.chain_id AND c.address = u.address
+          AND s.chain_id = u.chain_id AND s.address = u.address
+          AND c.source_code = s.source_code
+        "#,
+        STAGED
+    ))
+    .bind(&parsed.0)
+    .bind(&parsed.1)
+    .bind(&parsed.2)
+    .bind(&parsed.3)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Create the staging table and `COPY` a contracts CSV into it
+async fn stage_csv(tx: &mut PgConnection, copy: &str, csv_path: &str) -> Result<(), sqlx::Error> {
+    let file = tokio::fs::File::open(csv_path).await?;
+
+    tx.execute(CREATE_STAGING_TABLE).await?;
+    let mut copy_in = tx.copy_in_raw(copy).await?;
+    copy_in.read_from(file).await?;
+    copy_in.finish().await?;
+
+    Ok(())
+}
+
+/// Rows staged for one merge; dropped when the transaction ends
+const CREATE_STAGING_TABLE: &str = r#"
+CREATE TEMP TABLE _contracts_staging (
+    seq BIGSERIAL,
+    address TEXT,
+    chain TEXT,
+    chain_id INTEGER,
+    name TEXT,
+    symbol TEXT,
+    source_code TEXT,
+    abi TEXT,
+    is_proxy TEXT,
+    implementation_address TEXT,
+    protocol TEXT,
+    contract_type TEXT,
+    version TEXT,
+    description TEXT,
+    updated_at BIGINT,
+    compiler_version TEXT,
+    optimization_used TEXT,
+    optimization_runs INTEGER,
+    evm_version TEXT,
+    constructor_arguments TEXT,
+    library TEXT,
+    license_type TEXT,
+    swarm_source TEXT
+) ON COMMIT DROP
+"#;
+
+/// Columns of a contracts CSV that can be staged
+const STAGING_COLUMNS: &[&str] = &[
+    "address",
+    "chain",
+    "chain_id",
+    "name",
+    "symbol",
+    "source_code",
+    "abi",
+    "is_proxy",
+    "implementation_address",
+    "protocol",
+    "contract_type",
+    "version",
+    "description",
+    "updated_at",
+    "compiler_version",
+    "optimization_used",
+    "optimization_runs",
+    "evm_version",
+    "constructor_arguments",
+    "library",
+    "license_type",
+    "swarm_source",
+];
+
+/// The staged rows to merge: the last row for each contract, typed like `contracts`
+/// and with addresses lowercased as `Address` stores them
+const STAGED: &str = r#"
+(
+    SELECT DISTINCT ON (chain_id, lower(address))
+        lower(address) AS address, chain, chain_id, name, symbol, source_code, abi,
+        is_proxy::BOOLEAN AS is_proxy, lower(implementation_address) AS implementation_address,
+        protocol, contract_type, version, description, updated_at, compiler_version,
+        optimization_used::BOOLEAN AS optimization_used, optimization_runs, evm_version,
+        constructor_arguments, library, license_type, swarm_source
+    FROM _contracts_staging
+    WHERE address IS NOT NULL AND chain_id IS NOT NULL
+    ORDER BY chain_id, lower(address), seq DESC
+) AS s
+"#;
+
+/// Outcome of merging a batch into `contracts`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MergeSummary {
+    pub inserted: u64,
+    pub updated: u64,
+    pub unchanged: u64,
+}
+
+impl MergeSummary {
+    pub fn total(&self) -> u64 {
+        self.inserted + self.updated + self.unchanged
+    }
+
+    pub fn add(&mut self, other: MergeSummary) {
+        self.inserted += other.inserted;
+        self.updated += other.updated;
+        self.unchanged += other.unchanged;
+    }
+}
+
+impl fmt::Display for MergeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} updated, {} unchanged",
+            self.inserted, self.updated, self.unchanged
+        )
+    }
+}
+
+/// What happens when a staged contract is already in `contracts`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Staged values replace stored ones; empty values never erase data
+    #[default]
+    Overwrite,
+    /// Only fill columns that are still empty in the database
+    FillMissing,
+    /// Leave existing contracts untouched
+    Skip,
+    /// Overwrite only if the staged `updated_at` is newer than the stored one
+    /// (rows without `updated_at` count as updated now)
+    NewerWins,
+}
+
+/// Columns an import can write, for `--protect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MergeColumn {
+    Chain,
+    Name,
+    Symbol,
+    Description,
+    SourceCode,
+    Abi,
+    IsProxy,
+    ImplementationAddress,
+    Protocol,
+    ContractType,
+    Version,
+    CompilerVersion,
+    OptimizationUsed,
+    OptimizationRuns,
+    EvmVersion,
+    ConstructorArguments,
+    Library,
+    LicenseType,
+    SwarmSource,
+}
+
+impl MergeColumn {
+    pub const ALL: [MergeColumn; 19] = [
+        MergeColumn::Chain,
+        MergeColumn::Name,
+        MergeColumn::Symbol,
+        MergeColumn::Description,
+        MergeColumn::SourceCode,
+        MergeColumn::Abi,
+        MergeColumn::IsProxy,
+        MergeColumn::ImplementationAddress,
+        MergeColumn::Protocol,
+        MergeColumn::ContractType,
+        MergeColumn::Version,
+        MergeColumn::CompilerVersion,
+        MergeColumn::OptimizationUsed,
+        MergeColumn::OptimizationRuns,
+        MergeColumn::EvmVersion,
+        MergeColumn::ConstructorArguments,
+        MergeColumn::Library,
+        MergeColumn::LicenseType,
+        MergeColumn::SwarmSource,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MergeColumn::Chain => "chain",
+            MergeColumn::Name => "name",
+            MergeColumn::Symbol => "symbol",
+            MergeColumn::Description => "description",
+            MergeColumn::SourceCode => "source_code",
+            MergeColumn::Abi => "abi",
+            MergeColumn::IsProxy => "is_proxy",
+            MergeColumn::ImplementationAddress => "implementation_address",
+            MergeColumn::Protocol => "protocol",
+            MergeColumn::ContractType => "contract_type",
+            MergeColumn::Version => "version",
+            MergeColumn::CompilerVersion => "compiler_version",
+            MergeColumn::OptimizationUsed => "optimization_used",
+            MergeColumn::OptimizationRuns => "optimization_runs",
+            MergeColumn::EvmVersion => "evm_version",
+            MergeColumn::ConstructorArguments => "constructor_arguments",
+            MergeColumn::Library => "library",
+            MergeColumn::LicenseType => "license_type",
+            MergeColumn::SwarmSource => "swarm_source",
+        }
+    }
+
+    /// The default a new contract gets when the column is missing, which
+    /// counts as missing when merging
+    fn placeholder(&self) -> Option<&'static str> {
+        match self {
+            MergeColumn::Name => Some("'Unknown'"),
+            MergeColumn::SourceCode => Some("''"),
+            MergeColumn::Abi => Some("'[]'"),
+            _ => None,
+        }
+    }
+
+    /// `{alias}.{column}`, NULL when it holds the placeholder
+    fn known_value(&self, alias: &str) -> String {
+        match self.placeholder() {
+            Some(placeholder) => format!("NULLIF({}.{}, {})", alias, self.as_str(), placeholder),
+            None => format!("{}.{}", alias, self.as_str()),
+        }
+    }
+}
+
+/// How staged rows are merged into `contracts`, shared by `import`, `seed`
+/// and the fetch pipeline
+#[derive(Debug, Clone, Default)]
+pub struct MergePolicy {
+    pub on_conflict: ConflictPolicy,
+    /// Curated columns that are only ever filled, never replaced
+    pub protected: Vec<MergeColumn>,
+}
+
+impl MergePolicy {
+    /// The value `column` of an existing contract `c` takes when merging staged
+    /// row `s`. Placeholders such as an empty `source_code` count as missing on
+    /// both sides.
+    fn merged_value(&self, column: MergeColumn) -> String {
+        let (stored, staged) = (column.known_value("c"), column.known_value("s"));
+        let fill_only =
+            self.on_conflict == ConflictPolicy::FillMissing || self.protected.contains(&column);
+        let (first, second) = if fill_only {
+            (stored, staged)
+        } else {
+            (staged, stored)
+        };
+        format!("COALESCE({}, {}, c.{})", first, second, column.as_str())
+    }
+
+    /// Matches staged rows `s` to the existing contracts `c` they would change;
+    /// `$1` is the merge time
+    fn update_condition(&self) -> String {
+        let current: Vec<String> = MergeColumn::ALL
+            .iter()
+            .map(|column| format!("c.{}", column.as_str()))
+            .collect();
+        let merged: Vec<String> = MergeColumn::ALL
+            .iter()
+            .map(|&column| self.merged_value(column))
+            .collect();
+        let newer = if self.on_conflict == ConflictPolicy::NewerWins {
+            "AND COALESCE(s.updated_at, $1) > c.updated_at"
+        } else {
+            ""
+        };
+
+        format!(
+            "c.chain_id = s.chain_id AND c.address = s.address {}
+              AND ({}) IS DISTINCT FROM ({})",
+            newer,
+            current.join(", "),
+            merged.join(", ")
         )
-        .bind(&contract.address)
-        .bind(&contract.chain)
-        .bind(contract.chain_id)
-        .bind(&contract.source_code)
-        .bind(&contract.abi)
-        .bind(&contract.name)
-        .bind(&contract.symbol)
-        .bind(contract.is_proxy)
-        .bind(&contract.implementation_address)
-        .bind(&contract.protocol)
-        .bind(&contract.contract_type)
-        .bind(&contract.version)
+    }
+}
+
+/// Replace the staged `chain` of every registered chain id with its canonical
+/// name, whatever the input called it
+async fn normalize_chains(tx: &mut PgConnection) -> Result<(), sqlx::Error> {
+    let (ids, names): (Vec<i32>, Vec<&str>) = chains::registry()
+        .iter()
+        .map(|chain| (chain.chain_id, chain.name.as_str()))
+        .unzip();
+
+    sqlx::query(
+        r#"
+        UPDATE _contracts_staging s SET chain = r.name
+        FROM UNNEST($1::INTEGER[], $2::TEXT[]) AS r(chain_id, name)
+        WHERE s.chain_id = r.chain_id AND s.chain IS DISTINCT FROM r.name
+        "#,
+    )
+    .bind(ids)
+    .bind(names)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Merge `_contracts_staging` into `contracts` according to `policy`.
+///
+/// A NULL staged value never replaces stored data, whatever the policy. New
+/// contracts get placeholders for missing `name`, `source_code`, `abi` and
+/// `is_proxy`; stored placeholders are filled like NULLs. Rows whose values would not change are left alone,
+/// `updated_at` included.
+async fn merge_staged(
+    tx: &mut PgConnection,
+    policy: &MergePolicy,
+) -> Result<MergeSummary, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp();
+    normalize_chains(tx).await?;
+
+    let (staged,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {}", STAGED))
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let updated = if policy.on_conflict == ConflictPolicy::Skip {
+        0
+    } else {
+        let assignments: Vec<String> = MergeColumn::ALL
+            .iter()
+            .map(|&column| format!("{} = {}", column.as_str(), policy.merged_value(column)))
+            .collect();
+
+        sqlx::query(&format!(
+            r#"
+            UPDATE contracts c SET
+                {},
+                updated_at = GREATEST(c.updated_at, COALESCE(s.updated_at, $1))
+            FROM {}
+            WHERE {}
+            "#,
+            assignments.join(",\n                "),
+            STAGED,
+            policy.update_condition()
+        ))
         .bind(now)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+    };
+
+    let inserted = sqlx::query(&format!(
+        r#"
+        INSERT INTO contracts (
+            address, chain, chain_id, name, symbol, description, source_code, abi,
+            is_proxy, implementation_address, protocol, contract_type, version,
+            created_at, updated_at, compiler_version, optimization_used,
+            optimization_runs, evm_version, constructor_arguments, library,
+            license_type, swarm_source
+        )
+        SELECT
+            s.address, s.chain, s.chain_id, COALESCE(s.name, 'Unknown'), s.symbol,
+            s.description, COALESCE(s.source_code, ''), COALESCE(s.abi, '[]'),
+            COALESCE(s.is_proxy, false), s.implementation_address, s.protocol,
+            s.contract_type, s.version, $1, COALESCE(s.updated_at, $1),
+            s.compiler_version, s.optimization_used, s.optimization_runs, s.evm_version,
+            s.constructor_arguments, s.library, s.license_type, s.swarm_source
+        FROM {}
+        WHERE NOT EXISTS (
+            SELECT 1 FROM contracts c WHERE c.chain_id = s.chain_id AND c.address = s.address
+        )
+        ON CONFLICT (chain_id, address) DO NOTHING
+        "#,
+        STAGED
+    ))
+    .bind(now)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    Ok(MergeSummary {
+        inserted,
+        updated,
+        unchanged: (staged as u64).saturating_sub(inserted + updated),
+    })
+}
+
+/// Compute what [`merge_staged`] would do, without changing `contracts`
+async fn preview_staged(
+    tx: &mut PgConnection,
+    policy: &MergePolicy,
+) -> Result<MergePreview, sqlx::Error> {
+    let now = chrono::Utc::now().timestamp();
+    normalize_chains(tx).await?;
+
+    let (staged,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM {}", STAGED))
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let inserted: Vec<(i32, String)> = sqlx::query_as(&format!(
+        r#"
+        SELECT s.chain_id, s.address FROM {}
+        WHERE NOT EXISTS (
+            SELECT 1 FROM contracts c WHERE c.chain_id = s.chain_id AND c.address = s.address
+        )
+        ORDER BY s.chain_id, s.address
+        "#,
+        STAGED
+    ))
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut updated = Vec::new();
+    if policy.on_conflict != ConflictPolicy::Skip {
+        // Per column: changed?, then old and new values cut to a displayable size
+        let columns: Vec<String> = MergeColumn::ALL
+            .iter()
+            .map(|&column| {
+                let old = format!("c.{}::TEXT", column.as_str());
+                let new = format!("({})::TEXT", policy.merged_value(column));
+                format!(
+                    "{old} IS DISTINCT FROM {new}, left({old}, {len}), left({new}, {len})",
+                    old = old,
+                    new = new,
+                    len = PREVIEW_VALUE_CHARS
+                )
+            })
+            .collect();
+
+        let rows = sqlx::query(&format!(
+            r#"
+            SELECT s.chain_id, s.address, {}
+            FROM {} JOIN contracts c ON c.chain_id = s.chain_id AND c.address = s.address
+            WHERE {}
+            ORDER BY s.chain_id, s.address
+            "#,
+            columns.join(",\n                "),
+            STAGED,
+            policy.update_condition()
+        ))
         .bind(now)
-        .execute(&pool)
-        .await;
+        .fetch_all(&mut *tx)
+        .await?;
 
-        match result {
-            Ok(_) => {
-                println!("✓ Imported: {} ({})", contract.name, contract.address);
-                imported += 1;
-            }
-            Err(e) => {
-                eprintln!("✗ Failed to import {}: {}", contract.address, e);
+        for row in rows {
+            let mut changes = Vec::new();
+            for (i, column) in MergeColumn::ALL.iter().enumerate() {
+                let offset = 2 + i * 3;
+                if !row.try_get::<bool, _>(offset)? {
+                    continue;
+                }
+                let shown = !matches!(
+                    column,
+                    MergeColumn::SourceCode | MergeColumn::Abi | MergeColumn::ConstructorArguments
+                );
+                changes.push(ColumnChange {
+                    column: column.as_str(),
+                    old: if shown {
+                        row.try_get(offset + 1)?
+                    } else {
+                        None
+                    },
+                    new: if shown {
+                        row.try_get(offset + 2)?
+                    } else {
+                        None
+                    },
+                });
             }
+            updated.push(ContractChange {
+                chain_id: row.try_get(0)?,
+                address: row.try_get(1)?,
+                changes,
+            });
         }
     }
 
-    pool.close().await;
+    let unchanged = (staged as u64).saturating_sub((inserted.len() + updated.len()) as u64);
+
+    Ok(MergePreview {
+        inserted: inserted
+            .into_iter()
+            .map(|(chain_id, address)| ContractRef { chain_id, address })
+            .collect(),
+        updated,
+        unchanged,
+    })
+}
+
+/// `COPY` statement for a contracts CSV, with the columns named in its header
+fn copy_statement(csv_path: &str) -> Result<String> {
+    Ok(format!(
+        "COPY _contracts_staging ({}) FROM STDIN WITH (FORMAT csv, HEADER true)",
+        staging_columns(csv_path)?.join(", ")
+    ))
+}
+
+/// Staging columns named by the CSV header, in file order
+fn staging_columns(csv_path: &str) -> Result<Vec<String>> {
+    let mut reader =
+        csv::Reader::from_path(csv_path).with_context(|| format!("Failed to open {}", csv_path))?;
+    let headers = reader.headers()?;
+
+    let mut columns = Vec::new();
+    for header in headers {
+        let header = header.trim();
+        if !STAGING_COLUMNS.contains(&header) {
+            anyhow::bail!("{} has an unknown column '{}'", csv_path, header);
+        }
+        columns.push(header.to_string());
+    }
+    for required in ["address", "chain_id"] {
+        if !columns.iter().any(|c| c == required) {
+            anyhow::bail!("{} has no '{}' column", csv_path, required);
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Errors where the same statement may succeed on a fresh connection
+fn is_connection_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolTimedOut
+        | sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(db) => db.code().is_some_and(|code| {
+            // Class 08: connection exception; 57P01-03: server shutting down
+            code.starts_with("08") || matches!(code.as_ref(), "57P01" | "57P02" | "57P03")
+        }),
+        _ => false,
+    }
+}
 
-    Ok(imported)
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_millis(500)
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(Duration::from_secs(30))
 }
@@ -1,71 +1,458 @@
-use crate::models::{chain_id_to_name, ContractData, EtherscanResponse};
-use anyhow::{Context, Result};
+use crate::chains;
+use crate::key_pool::{ApiKeyPool, BenchReason};
+use crate::models::{Address, ContractData, EtherscanResponse, EtherscanResult};
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use serde_json::Value;
+use std::fmt;
+use std::future::Future;
 use std::time::Duration;
 use tokio::time::sleep;
 
+/// Etherscan free tier allows 5 requests per second
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 5.0;
+
+/// How long a key stays out of rotation after a rate-limit response
+const RATE_LIMIT_BENCH: Duration = Duration::from_secs(2);
+/// Daily quotas reset slowly, so a key that exhausted one sits out much longer
+const DAILY_LIMIT_BENCH: Duration = Duration::from_secs(60 * 60);
+const INVALID_KEY_BENCH: Duration = Duration::from_secs(15 * 60);
+
+/// Etherscan V2 endpoint, used for chains missing from the registry
+const DEFAULT_API_URL: &str = "https://api.etherscan.io/v2/api";
+
+/// EIP-1967 storage slot holding the beacon of a beacon proxy
+const BEACON_SLOT: &str = "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+
+/// Selector of `implementation()`, which a beacon answers with its logic contract
+const IMPLEMENTATION_SELECTOR: &str = "0x5c60da1b";
+
+/// Etherscan returns this in the ABI field for addresses without verified source
+const NOT_VERIFIED_ABI: &str = "Contract source code not verified";
+
+/// Broad classification of a failed Etherscan lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchErrorKind {
+    /// Network error, timeout, HTTP 5xx or an unparseable response
+    Transient,
+    /// HTTP 429 or "Max rate limit reached"
+    RateLimited,
+    /// The API key was rejected
+    InvalidApiKey,
+    /// The contract source is not verified on the explorer
+    NotVerified,
+    /// Etherscan returned no contract for the address
+    NotFound,
+    /// Any other API error
+    Other,
+}
+
+impl FetchErrorKind {
+    /// Whether another attempt could plausibly succeed
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            FetchErrorKind::Transient | FetchErrorKind::RateLimited
+        )
+    }
+
+    /// Stable name used in failure reports
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FetchErrorKind::Transient => "transient",
+            FetchErrorKind::RateLimited => "rate_limited",
+            FetchErrorKind::InvalidApiKey => "invalid_api_key",
+            FetchErrorKind::NotVerified => "not_verified",
+            FetchErrorKind::NotFound => "not_found",
+            FetchErrorKind::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FetchError {
+    pub kind: FetchErrorKind,
+    pub message: String,
+    /// Delay requested by the server via the Retry-After header
+    pub retry_after: Option<Duration>,
+    /// Number of attempts made before giving up
+    pub attempts: u32,
+}
+
+impl FetchError {
+    fn new(kind: FetchErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            retry_after: None,
+            attempts: 1,
+        }
+    }
+
+    /// Classify an API-level error from the `message`/`result` fields
+    fn from_api_message(text: String) -> Self {
+        let lower = text.to_lowercase();
+        let kind = if lower.contains("rate limit") {
+            FetchErrorKind::RateLimited
+        } else if lower.contains("invalid api key") || lower.contains("missing/invalid api key") {
+            FetchErrorKind::InvalidApiKey
+        } else if lower.contains("not verified") {
+            FetchErrorKind::NotVerified
+        } else if lower.contains("timeout") || lower.contains("timed out") {
+            FetchErrorKind::Transient
+        } else {
+            FetchErrorKind::Other
+        };
+
+        Self::new(kind, format!("Etherscan API error: {}", text))
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.attempts > 1 {
+            write!(f, "{} (after {} attempts)", self.message, self.attempts)
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+/// How failed requests are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts per address, including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the attempt following `attempt` (1-based).
+    ///
+    /// Exponential backoff with jitter in the upper half of the window, so
+    /// concurrent retries spread out while still growing. A server-provided
+    /// Retry-After always takes precedence when it is longer.
+    pub fn delay_for(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        let jittered = exp / 2 + exp.mul_f64(rand::thread_rng().gen::<f64>() / 2.0);
+
+        match retry_after {
+            Some(server_delay) => server_delay.max(jittered),
+            None => jittered,
+        }
+    }
+}
+
 pub struct EtherscanClient {
-    api_key: String,
+    api_keys: Vec<String>,
+    key_pool: ApiKeyPool,
     client: reqwest::Client,
+    retry_policy: RetryPolicy,
 }
 
 impl EtherscanClient {
-    pub fn new(api_key: String) -> Self {
+    pub fn new(api_keys: Vec<String>) -> Self {
         Self {
-            api_key,
-            client: reqwest::Client::new(),
+            key_pool: ApiKeyPool::new(api_keys.clone(), DEFAULT_REQUESTS_PER_SECOND),
+            api_keys,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("Failed to build HTTP client"),
+            retry_policy: RetryPolicy::default(),
         }
     }
 
+    /// Set the request budget of each API key
+    pub fn with_rate_limit(mut self, requests_per_second: f64) -> Self {
+        self.key_pool = ApiKeyPool::new(self.api_keys.clone(), requests_per_second);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Fetch a contract, retrying transient failures according to the retry policy
     pub async fn fetch_contract(
         &self,
-        address: &str,
+        address: &Address,
         chain_id: i32,
         protocol: Option<String>,
-    ) -> Result<ContractData> {
-        // Rate limit: 250ms between requests (Etherscan free tier: 5 req/sec)
-        sleep(Duration::from_millis(250)).await;
+    ) -> Result<ContractData, FetchError> {
+        self.with_retries(|api_key| {
+            self.request_contract(api_key, address, chain_id, protocol.clone())
+        })
+        .await
+    }
+
+    /// For an EIP-1967 beacon proxy, its beacon and the logic contract the
+    /// beacon currently points to; `None` if the beacon slot is empty
+    pub async fn beacon_implementation(
+        &self,
+        proxy: &Address,
+        chain_id: i32,
+    ) -> Result<Option<(Address, Address)>, FetchError> {
+        let slot = self
+            .with_retries(|api_key| {
+                let params = format!(
+                    "action=eth_getStorageAt&address={}&position={}&tag=latest",
+                    proxy, BEACON_SLOT
+                );
+                self.request_rpc(api_key, chain_id, params)
+            })
+            .await?;
+        let Some(beacon) = word_to_address(&slot) else {
+            return Ok(None);
+        };
+
+        let logic = self
+            .with_retries(|api_key| {
+                let params = format!(
+                    "action=eth_call&to={}&data={}&tag=latest",
+                    beacon, IMPLEMENTATION_SELECTOR
+                );
+                self.request_rpc(api_key, chain_id, params)
+            })
+            .await?;
+        let logic = word_to_address(&logic).ok_or_else(|| {
+            FetchError::new(
+                FetchErrorKind::Other,
+                format!("Beacon {} returned no implementation", beacon),
+            )
+        })?;
+
+        Ok(Some((beacon, logic)))
+    }
+
+    /// Run `request` with a leased API key, retrying transient failures
+    /// according to the retry policy
+    async fn with_retries<'s, T, F, Fut>(&'s self, request: F) -> Result<T, FetchError>
+    where
+        F: Fn(&'s str) -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let max_attempts = self.retry_policy.max_attempts.max(1);
+        let mut attempt = 1;
+
+        loop {
+            match self.request_once(&request).await {
+                Ok(value) => return Ok(value),
+                Err(mut e) => {
+                    e.attempts = attempt;
+                    let key_specific = matches!(
+                        e.kind,
+                        FetchErrorKind::RateLimited | FetchErrorKind::InvalidApiKey
+                    );
+                    let can_switch_key = key_specific && self.key_pool.key_count() > 1;
+
+                    if !(e.kind.is_retryable() || can_switch_key) || attempt >= max_attempts {
+                        return Err(e);
+                    }
+
+                    // Another key can take the retry straight away; the pool
+                    // itself waits if every key is benched
+                    if !can_switch_key {
+                        sleep(self.retry_policy.delay_for(attempt, e.retry_after)).await;
+                    }
+                    attempt += 1;
+                }
+            }
+        }
+    }
 
+    async fn request_once<'s, T, F, Fut>(&'s self, request: &F) -> Result<T, FetchError>
+    where
+        F: Fn(&'s str) -> Fut,
+        Fut: Future<Output = Result<T, FetchError>>,
+    {
+        let lease = self.key_pool.acquire().await.ok_or_else(|| {
+            FetchError::new(
+                FetchErrorKind::InvalidApiKey,
+                "Every Etherscan API key was rejected as invalid",
+            )
+        })?;
+
+        let result = request(lease.key).await;
+
+        if let Err(e) = &result {
+            match e.kind {
+                FetchErrorKind::RateLimited => {
+                    let duration = if e.message.to_lowercase().contains("daily") {
+                        DAILY_LIMIT_BENCH
+                    } else {
+                        e.retry_after.unwrap_or(RATE_LIMIT_BENCH)
+                    };
+                    self.key_pool
+                        .bench(lease.index, BenchReason::RateLimited, duration);
+                }
+                FetchErrorKind::InvalidApiKey => {
+                    self.key_pool
+                        .bench(lease.index, BenchReason::InvalidKey, INVALID_KEY_BENCH);
+                }
+                _ => {}
+            }
+        }
+
+        result
+    }
+
+    /// GET an Etherscan API URL, mapping transport and HTTP errors
+    async fn send(&self, url: &str) -> Result<reqwest::Response, FetchError> {
+        // Strip the URL from transport errors so API keys never reach logs
+        let response = self.client.get(url).send().await.map_err(|e| {
+            FetchError::new(
+                FetchErrorKind::Transient,
+                format!("Failed to send request to Etherscan: {}", e.without_url()),
+            )
+        })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let kind = if status.as_u16() == 429 {
+                FetchErrorKind::RateLimited
+            } else if status.is_server_error() {
+                FetchErrorKind::Transient
+            } else {
+                FetchErrorKind::Other
+            };
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+
+            let mut err = FetchError::new(kind, format!("Etherscan returned HTTP {}", status));
+            err.retry_after = retry_after;
+            return Err(err);
+        }
+
+        Ok(response)
+    }
+
+    /// Call Etherscan's JSON-RPC proxy module and return the hex `result`
+    async fn request_rpc(
+        &self,
+        api_key: &str,
+        chain_id: i32,
+        params: String,
+    ) -> Result<String, FetchError> {
         let url = format!(
-            "https://api.etherscan.io/v2/api?chainid={}&module=contract&action=getsourcecode&address={}&apikey={}",
-            chain_id, address, self.api_key
+            "{}?chainid={}&module=proxy&{}&apikey={}",
+            api_url(chain_id),
+            chain_id,
+            params,
+            api_key
         );
 
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to send request to Etherscan")?;
-
-        let data: EtherscanResponse = response
-            .json()
-            .await
-            .context("Failed to parse Etherscan response")?;
+        let data: Value = self.send(&url).await?.json().await.map_err(|e| {
+            FetchError::new(
+                FetchErrorKind::Transient,
+                format!("Failed to parse Etherscan response: {}", e.without_url()),
+            )
+        })?;
 
-        if data.status != "1" {
-            anyhow::bail!("Etherscan API error: {}", data.message);
+        if let Some(error) = data.get("error") {
+            let message = error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown error");
+            return Err(FetchError::new(
+                FetchErrorKind::Other,
+                format!("Etherscan RPC error: {}", message),
+            ));
         }
 
-        if data.result.is_empty() {
-            anyhow::bail!("No contract found at address {}", address);
+        match data.get("result").and_then(Value::as_str) {
+            Some(result) if result.starts_with("0x") => Ok(result.to_string()),
+            // API-level errors ("Max rate limit reached", ...) come back as plain text
+            Some(text) => Err(FetchError::from_api_message(text.to_string())),
+            None => Err(FetchError::new(
+                FetchErrorKind::Transient,
+                "Etherscan RPC response has no result",
+            )),
         }
+    }
 
-        let contract = &data.result[0];
+    async fn request_contract(
+        &self,
+        api_key: &str,
+        address: &Address,
+        chain_id: i32,
+        protocol: Option<String>,
+    ) -> Result<ContractData, FetchError> {
+        let url = format!(
+            "{}?chainid={}&module=contract&action=getsourcecode&address={}&apikey={}",
+            api_url(chain_id),
+            chain_id,
+            address,
+            api_key
+        );
 
-        // Detect if proxy
-        let is_proxy = !contract.implementation.is_empty() && contract.implementation != "0x";
-        let implementation_address = if is_proxy {
-            Some(contract.implementation.clone())
-        } else {
-            None
+        let response = self.send(&url).await?;
+
+        let data: EtherscanResponse = response.json().await.map_err(|e| {
+            FetchError::new(
+                FetchErrorKind::Transient,
+                format!("Failed to parse Etherscan response: {}", e.without_url()),
+            )
+        })?;
+
+        let contracts = match data.result {
+            EtherscanResult::Contracts(contracts) if data.status == "1" => contracts,
+            EtherscanResult::Message(text) => {
+                return Err(FetchError::from_api_message(format!(
+                    "{} - {}",
+                    data.message, text
+                )))
+            }
+            EtherscanResult::Contracts(_) => {
+                return Err(FetchError::from_api_message(data.message))
+            }
         };
 
+        let contract = contracts.first().ok_or_else(|| {
+            FetchError::new(
+                FetchErrorKind::NotFound,
+                format!("No contract found at address {}", address),
+            )
+        })?;
+
+        if contract.abi == NOT_VERIFIED_ABI {
+            return Err(FetchError::new(
+                FetchErrorKind::NotVerified,
+                format!("Contract source code not verified at {}", address),
+            ));
+        }
+
+        // Detect if proxy; Etherscan leaves `Implementation` empty (or "0x") otherwise
+        let implementation_address = Address::parse(&contract.implementation).ok();
+        let is_proxy = contract.proxy == "1" || implementation_address.is_some();
+
         // Detect contract type from name
         let contract_type = detect_contract_type(&contract.contract_name);
 
         Ok(ContractData {
-            address: address.to_lowercase(),
-            chain: chain_id_to_name(chain_id),
+            address: address.clone(),
+            chain: chains::registry().name(chain_id),
             chain_id,
             name: contract.contract_name.clone(),
             symbol: None, // Will be populated by RPC if needed
@@ -76,10 +463,46 @@ impl EtherscanClient {
             protocol,
             contract_type,
             version: None,
+            updated_at: Some(chrono::Utc::now().timestamp()),
+            compiler_version: non_empty(&contract.compiler_version),
+            optimization_used: match contract.optimization_used.as_str() {
+                "1" => Some(true),
+                "0" => Some(false),
+                _ => None,
+            },
+            optimization_runs: contract.runs.trim().parse().ok(),
+            evm_version: non_empty(&contract.evm_version),
+            constructor_arguments: non_empty(&contract.constructor_arguments),
+            library: non_empty(&contract.library),
+            license_type: non_empty(&contract.license_type),
+            swarm_source: non_empty(&contract.swarm_source),
         })
     }
 }
 
+/// Explorer API endpoint of a chain
+fn api_url(chain_id: i32) -> &'static str {
+    chains::registry()
+        .get(chain_id)
+        .map_or(DEFAULT_API_URL, |chain| chain.explorer_api_url.as_str())
+}
+
+/// The address in the low 20 bytes of a 32-byte hex word; `None` when zero or empty
+fn word_to_address(word: &str) -> Option<Address> {
+    let hex = word.strip_prefix("0x")?;
+    if hex.len() < 40 {
+        return None;
+    }
+    let address = Address::parse(&format!("0x{}", &hex[hex.len() - 40..])).ok()?;
+    (address.as_str() != "0x0000000000000000000000000000000000000000").then_some(address)
+}
+
+/// Etherscan reports missing values as empty strings
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
 fn detect_contract_type(name: &str) -> Option<String> {
     let name_lower = name.to_lowercase();
 
@@ -99,3 +522,99 @@ fn detect_contract_type(name: &str) -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind_of(message: &str) -> FetchErrorKind {
+        FetchError::from_api_message(message.to_string()).kind
+    }
+
+    #[test]
+    fn classifies_api_messages() {
+        assert_eq!(
+            kind_of("NOTOK - Max rate limit reached"),
+            FetchErrorKind::RateLimited
+        );
+        assert_eq!(
+            kind_of("NOTOK - Max daily rate limit reached. 100000 (100%) of Daily Limit"),
+            FetchErrorKind::RateLimited
+        );
+        assert_eq!(
+            kind_of("NOTOK - Invalid API Key"),
+            FetchErrorKind::InvalidApiKey
+        );
+        assert_eq!(
+            kind_of("NOTOK - Missing/Invalid API Key, rate limit of 1/5sec applied"),
+            FetchErrorKind::RateLimited
+        );
+        assert_eq!(
+            kind_of("Contract source code not verified"),
+            FetchErrorKind::NotVerified
+        );
+        assert_eq!(kind_of("Query Timeout occured"), FetchErrorKind::Transient);
+        assert_eq!(
+            kind_of("NOTOK - Invalid address format"),
+            FetchErrorKind::Other
+        );
+    }
+
+    #[test]
+    fn only_transient_and_rate_limited_are_retryable() {
+        assert!(FetchErrorKind::Transient.is_retryable());
+        assert!(FetchErrorKind::RateLimited.is_retryable());
+        assert!(!FetchErrorKind::InvalidApiKey.is_retryable());
+        assert!(!FetchErrorKind::NotVerified.is_retryable());
+        assert!(!FetchErrorKind::NotFound.is_retryable());
+        assert!(!FetchErrorKind::Other.is_retryable());
+    }
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn delay_doubles_with_jitter_in_the_upper_half() {
+        for (attempt, window) in [(1, 100), (2, 200), (3, 400), (4, 800)] {
+            let window = Duration::from_millis(window);
+            for _ in 0..50 {
+                let delay = policy().delay_for(attempt, None);
+                assert!(
+                    delay >= window / 2 && delay <= window,
+                    "attempt {}: {:?}",
+                    attempt,
+                    delay
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        for attempt in [5, 10, 40, u32::MAX] {
+            let delay = policy().delay_for(attempt, None);
+            assert!(
+                delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1),
+                "attempt {}: {:?}",
+                attempt,
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn longer_retry_after_wins() {
+        // Even past the cap: the server knows when the limit resets
+        let server = Duration::from_secs(30);
+        assert_eq!(policy().delay_for(1, Some(server)), server);
+
+        // A shorter one doesn't cut the backoff
+        let delay = policy().delay_for(3, Some(Duration::from_millis(1)));
+        assert!(delay >= Duration::from_millis(200), "{:?}", delay);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::models::{Address, ContractData, ContractMetadata};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use futures::TryStreamExt;
+use serde::Serialize;
+use sqlx::postgres::{PgPool, PgRow, Postgres};
+use sqlx::{QueryBuilder, Row};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Output layouts of `export`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Full `ContractData` rows, as read by `import` and `seed`
+    Csv,
+    /// `ContractMetadata` rows without source code or ABI, like `contracts-metadata.csv`
+    MetadataCsv,
+    /// One full `ContractData` JSON object per line
+    Jsonl,
+}
+
+/// Which contracts to export; unset fields match everything
+#[derive(Debug, Default)]
+pub struct ExportFilter {
+    pub chain_ids: Vec<i32>,
+    pub protocol: Option<String>,
+    pub contract_type: Option<String>,
+    /// Unix time; only contracts updated at or after it
+    pub updated_since: Option<i64>,
+}
+
+/// Stream the matching rows of `contracts` into `path`, one row at a time.
+/// Returns the number of contracts written.
+pub async fn export_contracts(
+    pool: &PgPool,
+    filter: &ExportFilter,
+    format: ExportFormat,
+    path: &str,
+) -> Result<usize> {
+    let columns = match format {
+        ExportFormat::MetadataCsv => {
+            "address, chain, chain_id, name, symbol, is_proxy, implementation_address, \
+             protocol, contract_type, version, created_at, updated_at"
+        }
+        ExportFormat::Csv | ExportFormat::Jsonl => {
+            "address, chain, chain_id, name, symbol, source_code, abi, is_proxy, \
+             implementation_address, protocol, contract_type, version, updated_at, \
+             compiler_version, optimization_used, optimization_runs, evm_version, \
+             constructor_arguments, library, license_type, swarm_source"
+        }
+    };
+
+    let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM contracts", columns));
+    push_filter(&mut query, filter);
+    query.push(" ORDER BY chain_id, address");
+
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path))?;
+    let mut output = ExportWriter::new(format, BufWriter::new(file));
+    let mut rows = query.build().fetch(pool);
+    let mut written = 0;
+
+    while let Some(row) = rows.try_next().await? {
+        match format {
+            ExportFormat::MetadataCsv => output.write(&metadata_from_row(&row)?)?,
+            ExportFormat::Csv | ExportFormat::Jsonl => output.write(&contract_from_row(&row)?)?,
+        }
+        written += 1;
+    }
+
+    output.finish()?;
+    Ok(written)
+}
+
+fn push_filter(query: &mut QueryBuilder<'_, Postgres>, filter: &ExportFilter) {
+    let mut separated = " WHERE ";
+    let mut next = |query: &mut QueryBuilder<'_, Postgres>| {
+        query.push(separated);
+        separated = " AND ";
+    };
+
+    if !filter.chain_ids.is_empty() {
+        next(query);
+        query
+            .push("chain_id = ANY(")
+            .push_bind(filter.chain_ids.clone())
+            .push(")");
+    }
+    if let Some(protocol) = &filter.protocol {
+        next(query);
+        query.push("protocol = ").push_bind(protocol.clone());
+    }
+    if let Some(contract_type) = &filter.contract_type {
+        next(query);
+        query
+            .push("contract_type = ")
+            .push_bind(contract_type.clone());
+    }
+    if let Some(updated_since) = filter.updated_since {
+        next(query);
+        query.push("updated_at >= ").push_bind(updated_since);
+    }
+}
+
+enum ExportWriter {
+    Csv(Box<csv::Writer<BufWriter<File>>>),
+    Jsonl(BufWriter<File>),
+}
+
+impl ExportWriter {
+    fn new(format: ExportFormat, file: BufWriter<File>) -> Self {
+        match format {
+            ExportFormat::Csv | ExportFormat::MetadataCsv => {
+                ExportWriter::Csv(Box::new(csv::Writer::from_writer(file)))
+            }
+            ExportFormat::Jsonl => ExportWriter::Jsonl(file),
+        }
+    }
+
+    fn write<T: Serialize>(&mut self, row: &T) -> Result<()> {
+        match self {
+            ExportWriter::Csv(writer) => writer.serialize(row)?,
+            ExportWriter::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, row)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            ExportWriter::Csv(mut writer) => writer.flush()?,
+            ExportWriter::Jsonl(mut writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+fn contract_from_row(row: &PgRow) -> Result<ContractData, sqlx::Error> {
+    Ok(ContractData {
+        address: address(row, "address")?,
+        chain: row.try_get("chain")?,
+        chain_id: row.try_get("chain_id")?,
+        name: row.try_get("name")?,
+        symbol: row.try_get("symbol")?,
+        source_code: row.try_get("source_code")?,
+        abi: row.try_get("abi")?,
+        is_proxy: row.try_get("is_proxy")?,
+        implementation_address: row
+            .try_get::<Option<String>, _>("implementation_address")?
+            .map(|value| parse_address("implementation_address", &value))
+            .transpose()?,
+        protocol: row.try_get("protocol")?,
+        contract_type: row.try_get("contract_type")?,
+        version: row.try_get("version")?,
+        updated_at: row.try_get("updated_at")?,
+        compiler_version: row.try_get("compiler_version")?,
+        optimization_used: row.try_get("optimization_used")?,
+        optimization_runs: row.try_get("optimization_runs")?,
+        evm_version: row.try_get("evm_version")?,
+        constructor_arguments: row.try_get("constructor_arguments")?,
+        library: row.try_get("library")?,
+        license_type: row.try_get("license_type")?,
+        swarm_source: row.try_get("swarm_source")?,
+    })
+}
+
+fn metadata_from_row(row: &PgRow) -> Result<ContractMetadata, sqlx::Error> {
+    Ok(ContractMetadata {
+        address: address(row, "address")?,
+        chain: row.try_get("chain")?,
+        chain_id: row.try_get("chain_id")?,
+        name: row.try_get("name")?,
+        symbol: row.try_get("symbol")?,
+        is_proxy: row.try_get("is_proxy")?,
+        implementation_address: row
+            .try_get::<Option<String>, _>("implementation_address")?
+            .map(|value| parse_address("implementation_address", &value))
+            .transpose()?,
+        protocol: row.try_get("protocol")?,
+        contract_type: row.try_get("contract_type")?,
+        version: row.try_get("version")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
+
+fn address(row: &PgRow, column: &str) -> Result<Address, sqlx::Error> {
+    parse_address(column, &row.try_get::<String, _>(column)?)
+}
+
+fn parse_address(column: &str, value: &str) -> Result<Address, sqlx::Error> {
+    Address::parse(value).map_err(|e| sqlx::Error::ColumnDecode {
+        index: column.to_string(),
+        source: e.into(),
+    })
+}
@@ -0,0 +1,142 @@
+use crate::chains;
+use crate::etherscan::FetchError;
+use crate::models::{Address, ContractMetadata, CuratedAddress};
+use anyhow::{Context, Result};
+use csv::{Reader, Writer};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+
+/// One row of a `--failures-out` report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailureRecord {
+    pub address: Address,
+    pub chain_id: i32,
+    pub protocol: Option<String>,
+    pub error_kind: String,
+    pub message: String,
+    pub attempts: u32,
+    /// Known metadata of the target, so a retry applies it like the original
+    /// run; empty for targets without metadata and in older reports
+    #[serde(default)]
+    pub is_proxy: Option<bool>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub implementation_address: Option<Address>,
+    #[serde(default)]
+    pub contract_type: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl FailureRecord {
+    pub fn new(target: &CuratedAddress, error: &FetchError) -> Self {
+        let metadata = target.metadata.as_ref();
+        Self {
+            address: target.address.clone(),
+            chain_id: target.chain_id,
+            protocol: target.protocol.clone(),
+            error_kind: error.kind.as_str().to_string(),
+            message: error.message.clone(),
+            attempts: error.attempts,
+            is_proxy: metadata.map(|m| m.is_proxy),
+            name: metadata.map(|m| m.name.clone()),
+            symbol: metadata.and_then(|m| m.symbol.clone()),
+            implementation_address: metadata.and_then(|m| m.implementation_address.clone()),
+            contract_type: metadata.and_then(|m| m.contract_type.clone()),
+            version: metadata.and_then(|m| m.version.clone()),
+        }
+    }
+
+    pub fn into_target(self) -> CuratedAddress {
+        // `is_proxy` is always written for targets that carried metadata
+        let metadata = self.is_proxy.map(|is_proxy| ContractMetadata {
+            address: self.address.clone(),
+            chain: chains::registry().name(self.chain_id),
+            chain_id: self.chain_id,
+            name: self.name.unwrap_or_default(),
+            symbol: self.symbol,
+            is_proxy,
+            implementation_address: self.implementation_address,
+            protocol: self.protocol.clone(),
+            contract_type: self.contract_type,
+            version: self.version,
+            created_at: None,
+            updated_at: None,
+        });
+
+        CuratedAddress {
+            address: self.address,
+            chain_id: self.chain_id,
+            protocol: self.protocol,
+            metadata,
+        }
+    }
+}
+
+/// CSV report of addresses that could not be fetched. Rows go to a temporary
+/// file that replaces `path` in [`FailureReport::finish`], so a run can retry
+/// the report it is rewriting.
+pub struct FailureReport {
+    path: String,
+    temp_path: String,
+    writer: Writer<File>,
+    recorded: usize,
+}
+
+impl FailureReport {
+    pub fn create(path: &str) -> Result<Self> {
+        let temp_path = format!("{}.tmp", path);
+        let writer = Writer::from_path(&temp_path)
+            .with_context(|| format!("Failed to create failure report {}", temp_path))?;
+        Ok(Self {
+            path: path.to_string(),
+            temp_path,
+            writer,
+            recorded: 0,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn record(&mut self, target: &CuratedAddress, error: &FetchError) -> Result<()> {
+        self.write(&FailureRecord::new(target, error))
+    }
+
+    /// Keep a failure from an earlier report that this run does not retry
+    pub fn carry_over(&mut self, record: &FailureRecord) -> Result<()> {
+        self.write(record)
+    }
+
+    fn write(&mut self, record: &FailureRecord) -> Result<()> {
+        self.writer.serialize(record)?;
+        self.writer.flush()?;
+        self.recorded += 1;
+        Ok(())
+    }
+
+    /// Move the report into place; returns how many failures it holds
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.flush()?;
+        drop(self.writer);
+        fs::rename(&self.temp_path, &self.path)
+            .with_context(|| format!("Failed to write failure report {}", self.path))?;
+        Ok(self.recorded)
+    }
+}
+
+pub fn read_failures(path: &str) -> Result<Vec<FailureRecord>> {
+    let mut reader = Reader::from_path(path)
+        .with_context(|| format!("Failed to open failure report {}", path))?;
+
+    let mut records = Vec::new();
+    for result in reader.deserialize() {
+        records.push(result?);
+    }
+
+    Ok(records)
+}
@@ -0,0 +1,121 @@
+use crate::rate_limiter::RateLimiter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep_until;
+
+/// Why a key was taken out of rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BenchReason {
+    RateLimited,
+    InvalidKey,
+}
+
+struct PooledKey {
+    key: String,
+    limiter: RateLimiter,
+    benched: Mutex<Option<(Instant, BenchReason)>>,
+}
+
+impl PooledKey {
+    /// Bench expiry if the key is currently benched
+    fn benched_until(&self, now: Instant) -> Option<(Instant, BenchReason)> {
+        let mut benched = self.benched.lock().unwrap();
+        match *benched {
+            Some((until, _)) if until <= now => {
+                *benched = None;
+                None
+            }
+            other => other,
+        }
+    }
+}
+
+/// A key handed out by [`ApiKeyPool::acquire`]
+pub struct KeyLease<'a> {
+    pub index: usize,
+    pub key: &'a str,
+}
+
+/// Etherscan API keys used round-robin, each with its own rate budget
+pub struct ApiKeyPool {
+    keys: Vec<PooledKey>,
+    next: AtomicUsize,
+}
+
+impl ApiKeyPool {
+    pub fn new(keys: Vec<String>, requests_per_second: f64) -> Self {
+        assert!(
+            !keys.is_empty(),
+            "at least one Etherscan API key is required"
+        );
+
+        Self {
+            keys: keys
+                .into_iter()
+                .map(|key| PooledKey {
+                    key,
+                    limiter: RateLimiter::new(requests_per_second),
+                    benched: Mutex::new(None),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Take the next key that is not benched and wait for its rate budget.
+    ///
+    /// Waits for the earliest bench to expire when every key is benched, but
+    /// returns `None` if all of them were rejected as invalid since waiting
+    /// would not help.
+    pub async fn acquire(&self) -> Option<KeyLease<'_>> {
+        loop {
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            let now = Instant::now();
+            let mut earliest: Option<Instant> = None;
+            let mut all_invalid = true;
+
+            for offset in 0..self.keys.len() {
+                let index = (start + offset) % self.keys.len();
+                let pooled = &self.keys[index];
+
+                match pooled.benched_until(now) {
+                    None => {
+                        pooled.limiter.acquire().await;
+                        return Some(KeyLease {
+                            index,
+                            key: &pooled.key,
+                        });
+                    }
+                    Some((until, reason)) => {
+                        all_invalid &= reason == BenchReason::InvalidKey;
+                        earliest = Some(earliest.map_or(until, |e| e.min(until)));
+                    }
+                }
+            }
+
+            if all_invalid {
+                return None;
+            }
+
+            if let Some(until) = earliest {
+                sleep_until(until.into()).await;
+            }
+        }
+    }
+
+    /// Take a key out of rotation for `duration`
+    pub fn bench(&self, index: usize, reason: BenchReason, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut benched = self.keys[index].benched.lock().unwrap();
+
+        // Never shorten an existing bench
+        if benched.is_none_or(|(current, _)| current < until) {
+            *benched = Some((until, reason));
+        }
+    }
+}
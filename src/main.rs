@@ -1,13 +1,29 @@
+mod address_source;
+mod chains;
+mod checkpoint;
+mod contract_sink;
 mod csv_handler;
 mod db_importer;
 mod etherscan;
+mod exporter;
+mod failures;
+mod key_pool;
+mod merge_preview;
+mod migrator;
 mod models;
+mod pipeline;
+mod rate_limiter;
+mod schema;
+mod source_files;
+mod validation;
 
+use address_source::AddressSource;
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use indicatif::{ProgressBar, ProgressStyle};
-use models::CuratedAddress;
-use std::fs;
+use checkpoint::Checkpoint;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use contract_sink::ContractSink;
+use std::path::Path;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "contract-csv-tool")]
@@ -15,23 +31,245 @@ use std::fs;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Chain registry JSON whose entries replace or extend the bundled chains.json
+    /// (or set CHAINS_FILE env var)
+    #[arg(long, global = true)]
+    chains: Option<String>,
+}
+
+/// Options shared by every command that calls Etherscan
+#[derive(Args)]
+struct FetchArgs {
+    /// Etherscan API key; repeat or comma-separate to rotate several keys
+    /// (or set ETHERSCAN_API_KEY env var, comma-separated)
+    #[arg(short, long, value_delimiter = ',')]
+    api_key: Vec<String>,
+
+    /// Maximum attempts per address before giving up on transient errors
+    #[arg(long, default_value = "5")]
+    max_attempts: u32,
+
+    /// Etherscan requests per second for each API key (or set ETHERSCAN_RPS env var, default: 5)
+    #[arg(long, value_parser = parse_rps)]
+    rps: Option<f64>,
+
+    /// Number of Etherscan requests kept in flight at once
+    #[arg(long, default_value = "4")]
+    concurrency: usize,
+
+    /// Checkpoint file recording completed, failed and pending addresses
+    /// (default: <input>.checkpoint.json)
+    #[arg(long)]
+    checkpoint: Option<String>,
+
+    /// Skip addresses already fetched by an earlier run, according to the
+    /// checkpoint and the existing output CSV or database rows
+    #[arg(long)]
+    resume: bool,
+
+    /// Write addresses that could not be fetched to this CSV
+    #[arg(long)]
+    failures_out: Option<String>,
+
+    /// Also fetch the implementation behind every proxy as its own contract
+    #[arg(long)]
+    follow_proxies: bool,
+
+    /// With --follow-proxies, how many proxy hops to follow from a listed address
+    #[arg(long, default_value = "3")]
+    max_proxy_depth: usize,
+}
+
+impl FetchArgs {
+    fn build_client(&self) -> Result<etherscan::EtherscanClient> {
+        let mut api_keys = self.api_key.clone();
+        if api_keys.is_empty() {
+            api_keys = std::env::var("ETHERSCAN_API_KEY")
+                .map(|v| v.split(',').map(str::to_string).collect())
+                .unwrap_or_default();
+        }
+        api_keys = api_keys
+            .into_iter()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .collect();
+        assert!(
+            !api_keys.is_empty(),
+            "ETHERSCAN_API_KEY must be provided via --api-key or environment variable"
+        );
+
+        let rps = match (self.rps, std::env::var("ETHERSCAN_RPS")) {
+            (Some(rps), _) => rps,
+            (None, Ok(value)) => {
+                parse_rps(&value).map_err(|e| anyhow::anyhow!("Invalid ETHERSCAN_RPS: {}", e))?
+            }
+            (None, Err(_)) => etherscan::DEFAULT_REQUESTS_PER_SECOND,
+        };
+
+        Ok(etherscan::EtherscanClient::new(api_keys)
+            .with_retry_policy(etherscan::RetryPolicy {
+                max_attempts: self.max_attempts,
+                ..Default::default()
+            })
+            .with_rate_limit(rps))
+    }
+
+    /// Build the fetch pipeline; the checkpoint defaults to `<input>.checkpoint.json`
+    fn pipeline(&self, input: &str) -> Result<pipeline::FetchPipeline> {
+        let checkpoint_path = self
+            .checkpoint
+            .clone()
+            .unwrap_or_else(|| format!("{}.checkpoint.json", input));
+
+        Ok(pipeline::FetchPipeline {
+            client: self.build_client()?,
+            concurrency: self.concurrency,
+            checkpoint: Checkpoint::open(checkpoint_path, self.resume)?,
+            failures: self
+                .failures_out
+                .as_deref()
+                .map(failures::FailureReport::create)
+                .transpose()?,
+            resume: self.resume,
+            follow_proxies: self.follow_proxies.then_some(self.max_proxy_depth),
+        })
+    }
+}
+
+/// Options shared by every command that writes to the database
+#[derive(Args)]
+struct DbArgs {
+    /// Database URL (or set DATABASE_URL env var)
+    #[arg(short, long)]
+    database_url: Option<String>,
+
+    /// Maximum connections in the database pool
+    #[arg(long, default_value = "5")]
+    max_connections: u32,
+
+    /// Per-statement timeout in seconds (0 disables it)
+    #[arg(long, default_value = "60")]
+    statement_timeout: u64,
+
+    /// What to do with contracts that are already in the database
+    #[arg(long, value_enum, default_value = "overwrite")]
+    on_conflict: db_importer::ConflictPolicy,
+
+    /// Curated columns that are only filled when empty, never replaced
+    /// (e.g. protocol,contract-type,description,version)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    protect: Vec<db_importer::MergeColumn>,
+}
+
+impl DbArgs {
+    /// Open the connection pool shared by every database step of a command
+    async fn connect(&self) -> Result<db_importer::ContractImporter> {
+        let database_url = resolve_database_url(self.database_url.clone());
+
+        db_importer::ContractImporter::connect(
+            &database_url,
+            db_importer::ImporterOptions {
+                max_connections: self.max_connections,
+                statement_timeout: (self.statement_timeout > 0)
+                    .then(|| Duration::from_secs(self.statement_timeout)),
+                ..Default::default()
+            },
+        )
+        .await
+        .map(|importer| {
+            importer.with_merge_policy(db_importer::MergePolicy {
+                on_conflict: self.on_conflict,
+                protected: self.protect.clone(),
+            })
+        })
+    }
+}
+
+/// `migrate` actions besides applying pending migrations
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// List every migration with its applied/pending state
+    Status,
+
+    /// Revert applied migrations newer than a version, using their .down.sql files
+    Rollback {
+        /// Version to roll back to; it stays applied (0 reverts everything)
+        #[arg(long)]
+        to: i64,
+    },
+}
+
+#[derive(Subcommand)]
+enum SchemaAction {
+    /// Compare the live schema with the one the migrations produce; exits
+    /// non-zero on any difference
+    Diff {
+        /// Migrations directory
+        #[arg(long, default_value = "migrations")]
+        source: String,
+
+        /// Database URL to check (or set DATABASE_URL env var)
+        #[arg(short, long)]
+        database_url: Option<String>,
+
+        /// Empty database to apply the migrations to (default: a temporary
+        /// database created on the checked server)
+        #[arg(long)]
+        scratch_url: Option<String>,
+    },
+}
+
+/// `chains` actions
+#[derive(Subcommand)]
+enum ChainsAction {
+    /// List the chains of the registry
+    List,
+}
+
+/// Kinds of address input accepted by `fetch`
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormat {
+    /// `address,chain_id,protocol` lines
+    Curated,
+    /// Metadata CSV exported from the contracts table
+    MetadataCsv,
+    /// JSON array of {address, chain_id, protocol} objects
+    Json,
+    /// Rows already in the contracts table (--input is ignored)
+    Db,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Fetch contracts from Etherscan and save to CSV
+    /// Fetch contracts from Etherscan into CSV/JSONL files and/or the database
     Fetch {
-        /// Input file with curated addresses
+        /// Input file with addresses
         #[arg(short, long, default_value = "curated-addresses.txt")]
         input: String,
 
-        /// Output CSV file
-        #[arg(short, long, default_value = "contracts.csv")]
-        output: String,
+        /// Input format (default: inferred from the file extension)
+        #[arg(long, value_enum)]
+        input_format: Option<InputFormat>,
 
-        /// Etherscan API key (or set ETHERSCAN_API_KEY env var)
+        /// Output file, repeatable; `.jsonl` files get JSON lines, anything
+        /// else the contracts CSV (default: contracts.csv unless --to-db)
         #[arg(short, long)]
-        api_key: Option<String>,
+        output: Vec<String>,
+
+        /// Also import fetched contracts into the database
+        #[arg(long)]
+        to_db: bool,
+
+        #[command(flatten)]
+        fetch: FetchArgs,
+
+        #[command(flatten)]
+        db: DbArgs,
+
+        /// Batch size for database inserts (default: 50)
+        #[arg(short, long, default_value = "50")]
+        batch_size: usize,
     },
 
     /// Fetch contracts from Etherscan and import directly to database (no CSV)
@@ -40,13 +278,11 @@ enum Commands {
         #[arg(short, long, default_value = "curated-addresses.txt")]
         input: String,
 
-        /// Etherscan API key (or set ETHERSCAN_API_KEY env var)
-        #[arg(short, long)]
-        api_key: Option<String>,
+        #[command(flatten)]
+        fetch: FetchArgs,
 
-        /// Database URL (or set DATABASE_URL env var)
-        #[arg(short, long)]
-        database_url: Option<String>,
+        #[command(flatten)]
+        db: DbArgs,
 
         /// Batch size for database inserts (default: 50)
         #[arg(short, long, default_value = "50")]
@@ -59,11 +295,82 @@ enum Commands {
         #[arg(short, long, default_value = "contracts.csv")]
         input: String,
 
+        #[command(flatten)]
+        db: DbArgs,
+
+        /// Contracts upserted per statement and transaction (default: 500)
+        #[arg(short, long, default_value = "500")]
+        batch_size: usize,
+
+        /// Show what would be inserted or updated without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// With --dry-run, also write the preview as JSON to this file
+        #[arg(long, requires = "dry_run")]
+        json_out: Option<String>,
+    },
+
+    /// Seed the contracts table from a contracts CSV in one transaction
+    Seed {
+        /// Input CSV file
+        #[arg(short, long, default_value = "contracts.csv")]
+        input: String,
+
+        #[command(flatten)]
+        db: DbArgs,
+
+        /// Show what would be inserted or updated without writing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// With --dry-run, also write the preview as JSON to this file
+        #[arg(long, requires = "dry_run")]
+        json_out: Option<String>,
+    },
+
+    /// Export contracts from the database to CSV, metadata CSV or JSONL
+    Export {
+        /// Output file
+        #[arg(short, long)]
+        output: String,
+
+        /// Output layout (default: jsonl for .jsonl/.ndjson files, csv otherwise)
+        #[arg(short, long, value_enum)]
+        format: Option<exporter::ExportFormat>,
+
+        /// Only these chains; repeat or comma-separate
+        #[arg(long, value_delimiter = ',')]
+        chain_id: Vec<i32>,
+
+        /// Only contracts of this protocol
+        #[arg(long)]
+        protocol: Option<String>,
+
+        /// Only contracts of this type
+        #[arg(long)]
+        contract_type: Option<String>,
+
+        /// Only contracts updated at or after this time (unix seconds or YYYY-MM-DD)
+        #[arg(long, value_parser = parse_timestamp)]
+        updated_since: Option<i64>,
+
         /// Database URL (or set DATABASE_URL env var)
         #[arg(short, long)]
         database_url: Option<String>,
     },
 
+    /// Check curated address files and metadata CSVs without calling Etherscan
+    ValidateInput {
+        /// Files to check
+        #[arg(required = true)]
+        inputs: Vec<String>,
+
+        /// Input format (default: metadata-csv for .csv files, curated otherwise)
+        #[arg(short = 'f', long, value_enum)]
+        input_format: Option<InputFormat>,
+    },
+
     /// Show statistics about CSV file
     Stats {
         /// Input CSV file
@@ -77,13 +384,66 @@ enum Commands {
         #[arg(short, long, default_value = "contracts-metadata.csv")]
         input: String,
 
-        /// Etherscan API key (or set ETHERSCAN_API_KEY env var)
-        #[arg(short, long)]
-        api_key: Option<String>,
+        #[command(flatten)]
+        fetch: FetchArgs,
+
+        #[command(flatten)]
+        db: DbArgs,
+
+        /// Batch size for database inserts (default: 50)
+        #[arg(short, long, default_value = "50")]
+        batch_size: usize,
+    },
+
+    /// Apply pending migrations from the migrations directory
+    Migrate {
+        #[command(subcommand)]
+        action: Option<MigrateAction>,
+
+        /// Show what would be applied or reverted without changing the database
+        #[arg(long, global = true)]
+        dry_run: bool,
+
+        /// Migrations directory
+        #[arg(long, global = true, default_value = "migrations")]
+        source: String,
 
         /// Database URL (or set DATABASE_URL env var)
-        #[arg(short, long)]
+        #[arg(short, long, global = true)]
         database_url: Option<String>,
+    },
+
+    /// Inspect the live database schema
+    Schema {
+        #[command(subcommand)]
+        action: SchemaAction,
+    },
+
+    /// Inspect the chain registry
+    Chains {
+        #[command(subcommand)]
+        action: ChainsAction,
+    },
+
+    /// Fetch again the addresses listed in a --failures-out report
+    RetryFailures {
+        /// Failure report CSV
+        #[arg(short, long, default_value = "failures.csv")]
+        input: String,
+
+        /// Only retry failures of these kinds (e.g. transient,rate_limited)
+        #[arg(short, long, value_delimiter = ',')]
+        kind: Vec<String>,
+
+        /// Append fetched contracts to this CSV instead of importing to the database
+        #[arg(short, long)]
+        output: Option<String>,
+
+        #[command(flatten)]
+        fetch: FetchArgs,
+
+        #[command(flatten)]
+        db: DbArgs,
 
         /// Batch size for database inserts (default: 50)
         #[arg(short, long, default_value = "50")]
@@ -97,190 +457,425 @@ async fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
+    let chains_file = cli.chains.clone().or_else(|| std::env::var("CHAINS_FILE").ok());
+    chains::init(chains::ChainRegistry::load(chains_file.as_deref())?);
+
     match cli.command {
-        Commands::Fetch { input, output, api_key } => {
-            fetch_command(input, output, api_key).await?;
+        Commands::Fetch {
+            input,
+            input_format,
+            output,
+            to_db,
+            fetch,
+            db,
+            batch_size,
+        } => {
+            fetch_command(input, input_format, output, to_db, fetch, db, batch_size).await?;
+        }
+        Commands::FetchToDb { input, fetch, db, batch_size } => {
+            fetch_to_db_command(input, fetch, db, batch_size).await?;
+        }
+        Commands::Import {
+            input,
+            db,
+            batch_size,
+            dry_run,
+            json_out,
+        } => {
+            import_command(input, db, batch_size, dry_run, json_out).await?;
         }
-        Commands::FetchToDb { input, api_key, database_url, batch_size } => {
-            fetch_to_db_command(input, api_key, database_url, batch_size).await?;
+        Commands::Seed {
+            input,
+            db,
+            dry_run,
+            json_out,
+        } => {
+            seed_command(input, db, dry_run, json_out).await?;
         }
-        Commands::Import { input, database_url } => {
-            import_command(input, database_url).await?;
+        Commands::Export {
+            output,
+            format,
+            chain_id,
+            protocol,
+            contract_type,
+            updated_since,
+            database_url,
+        } => {
+            let filter = exporter::ExportFilter {
+                chain_ids: chain_id,
+                protocol,
+                contract_type,
+                updated_since,
+            };
+            export_command(output, format, filter, database_url).await?;
+        }
+        Commands::ValidateInput { inputs, input_format } => {
+            validate_input_command(inputs, input_format)?;
         }
         Commands::Stats { input } => {
             stats_command(input)?;
         }
-        Commands::FetchFromMetadataCsv { input, api_key, database_url, batch_size } => {
-            fetch_from_metadata_csv_command(input, api_key, database_url, batch_size).await?;
+        Commands::FetchFromMetadataCsv { input, fetch, db, batch_size } => {
+            fetch_from_metadata_csv_command(input, fetch, db, batch_size).await?;
+        }
+        Commands::Migrate {
+            action,
+            dry_run,
+            source,
+            database_url,
+        } => {
+            migrate_command(action, dry_run, source, database_url).await?;
+        }
+        Commands::Schema {
+            action:
+                SchemaAction::Diff {
+                    source,
+                    database_url,
+                    scratch_url,
+                },
+        } => {
+            schema_diff_command(source, database_url, scratch_url).await?;
+        }
+        Commands::Chains {
+            action: ChainsAction::List,
+        } => {
+            chains_list_command();
+        }
+        Commands::RetryFailures {
+            input,
+            kind,
+            output,
+            fetch,
+            db,
+            batch_size,
+        } => {
+            retry_failures_command(input, kind, output, fetch, db, batch_size).await?;
         }
     }
 
     Ok(())
 }
 
-async fn fetch_command(input: String, output: String, api_key: Option<String>) -> Result<()> {
-    let api_key = api_key
-        .or_else(|| std::env::var("ETHERSCAN_API_KEY").ok())
-        .expect("ETHERSCAN_API_KEY must be provided via --api-key or environment variable");
+async fn fetch_command(
+    input: String,
+    input_format: Option<InputFormat>,
+    outputs: Vec<String>,
+    to_db: bool,
+    fetch: FetchArgs,
+    db: DbArgs,
+    batch_size: usize,
+) -> Result<()> {
+    let format = input_format.unwrap_or_else(|| {
+        match Path::new(&input).extension().and_then(|ext| ext.to_str()) {
+            Some("json") => InputFormat::Json,
+            Some("csv") => InputFormat::MetadataCsv,
+            _ => InputFormat::Curated,
+        }
+    });
+
+    let importer = if to_db || matches!(format, InputFormat::Db) {
+        Some(db.connect().await?)
+    } else {
+        None
+    };
+
+    let source: Box<dyn AddressSource> = match format {
+        InputFormat::Curated => Box::new(address_source::CuratedFileSource { path: input.clone() }),
+        InputFormat::MetadataCsv => Box::new(address_source::MetadataCsvSource { path: input.clone() }),
+        InputFormat::Json => Box::new(address_source::JsonSource { path: input.clone() }),
+        InputFormat::Db => Box::new(address_source::DbSource {
+            importer: importer.clone().unwrap(),
+        }),
+    };
+
+    let mut outputs = outputs;
+    if outputs.is_empty() && !to_db {
+        outputs.push("contracts.csv".to_string());
+    }
+
+    let mut sinks = file_sinks(&outputs, fetch.resume);
+    if let Some(importer) = importer.as_ref().filter(|_| to_db) {
+        sinks.push(Box::new(contract_sink::PostgresSink::new(importer.clone(), batch_size)));
+    }
+
+    let summary = fetch.pipeline(&input)?.run(source.as_ref(), sinks).await?;
 
-    println!("📖 Reading curated addresses from: {}", input);
-    let content = fs::read_to_string(&input)?;
+    if let Some(importer) = importer {
+        importer.close().await;
+    }
 
-    let addresses: Vec<CuratedAddress> = content
-        .lines()
-        .filter_map(CuratedAddress::from_line)
-        .collect();
+    print_summaries(&summary)
+}
 
-    println!("✓ Found {} addresses to fetch\n", addresses.len());
+async fn fetch_to_db_command(
+    input: String,
+    fetch: FetchArgs,
+    db: DbArgs,
+    batch_size: usize,
+) -> Result<()> {
+    let source = address_source::CuratedFileSource { path: input.clone() };
+    let importer = db.connect().await?;
+    let sink = contract_sink::PostgresSink::new(importer.clone(), batch_size);
 
-    let client = etherscan::EtherscanClient::new(api_key);
+    let summary = fetch.pipeline(&input)?.run(&source, vec![Box::new(sink)]).await?;
+    importer.close().await;
 
-    let pb = ProgressBar::new(addresses.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("█▓▒░  "),
-    );
+    print_summaries(&summary)
+}
 
-    let mut contracts = Vec::new();
-
-    for addr in addresses {
-        pb.set_message(format!("Fetching {}", addr.address));
-
-        match client
-            .fetch_contract(&addr.address, addr.chain_id, addr.protocol)
-            .await
-        {
-            Ok(contract) => {
-                pb.println(format!(
-                    "✓ {} - {}",
-                    contract.name, contract.address
-                ));
-                contracts.push(contract);
-            }
-            Err(e) => {
-                pb.println(format!("✗ {} - Error: {}", addr.address, e));
+fn resolve_database_url(database_url: Option<String>) -> String {
+    database_url
+        .or_else(|| std::env::var("DATABASE_URL").ok())
+        .expect("DATABASE_URL must be provided via --database-url or environment variable")
+}
+
+/// CSV or JSONL sinks for the given output paths, chosen by extension
+fn file_sinks(outputs: &[String], append: bool) -> Vec<Box<dyn ContractSink>> {
+    outputs
+        .iter()
+        .map(|path| -> Box<dyn ContractSink> {
+            if path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+                Box::new(contract_sink::JsonlSink::new(path.clone(), append))
+            } else {
+                Box::new(contract_sink::CsvSink::new(path.clone(), append))
             }
-        }
+        })
+        .collect()
+}
 
-        pb.inc(1);
+/// Print what a run wrote; fails if any address could not be fetched
+fn print_summaries(summary: &pipeline::RunSummary) -> Result<()> {
+    println!();
+    for sink in &summary.sinks {
+        println!("💾 {} contracts written to {}", sink.written, sink.description);
     }
 
-    pb.finish_with_message("Done!");
-
-    println!("\n💾 Writing {} contracts to: {}", contracts.len(), output);
-    csv_handler::write_contracts_to_csv(&contracts, &output)?;
-
-    println!("✅ Success! {} contracts saved to {}", contracts.len(), output);
+    if summary.failed == 0 {
+        println!("✅ Success! All addresses fetched");
+        return Ok(());
+    }
 
-    Ok(())
+    match &summary.failures_path {
+        Some(path) => anyhow::bail!(
+            "{} addresses could not be fetched; see {} (retry with `retry-failures -i {}`)",
+            summary.failed,
+            path,
+            path
+        ),
+        None => anyhow::bail!(
+            "{} addresses could not be fetched; rerun with --failures-out to keep a report",
+            summary.failed
+        ),
+    }
 }
 
-async fn fetch_to_db_command(
+async fn import_command(
     input: String,
-    api_key: Option<String>,
-    database_url: Option<String>,
+    db: DbArgs,
     batch_size: usize,
+    dry_run: bool,
+    json_out: Option<String>,
 ) -> Result<()> {
-    let api_key = api_key
-        .or_else(|| std::env::var("ETHERSCAN_API_KEY").ok())
-        .expect("ETHERSCAN_API_KEY must be provided via --api-key or environment variable");
+    println!("📖 Reading contracts from: {}", input);
+    let mut contracts = csv_handler::read_contracts_from_csv(&input)?;
+    println!("💾 Importing to database...\n");
 
-    let database_url = database_url
-        .or_else(|| std::env::var("DATABASE_URL").ok())
-        .expect("DATABASE_URL must be provided via --database-url or environment variable");
+    let importer = db.connect().await?;
 
-    println!("📖 Reading curated addresses from: {}", input);
-    let content = fs::read_to_string(&input)?;
+    if dry_run {
+        let preview = importer.preview_import(&input, batch_size).await?;
+        importer.close().await;
+        return report_preview(&preview, json_out.as_deref());
+    }
 
-    let addresses: Vec<CuratedAddress> = content
-        .lines()
-        .filter_map(CuratedAddress::from_line)
-        .collect();
+    let mut summary = db_importer::MergeSummary::default();
+    loop {
+        let batch = csv_handler::next_batch(&mut contracts, batch_size)?;
+        if batch.is_empty() {
+            break;
+        }
+        let merged = importer.import(&batch).await?;
+        println!("✓ Imported batch: {}", merged);
+        summary.add(merged);
+    }
+    importer.close().await;
+
+    println!(
+        "\n✅ Success! Imported {} contracts to database ({})",
+        summary.total(),
+        summary
+    );
 
-    println!("✓ Found {} addresses to fetch", addresses.len());
-    println!("💾 Fetching and importing directly to database...\n");
+    Ok(())
+}
 
-    let client = etherscan::EtherscanClient::new(api_key);
+async fn seed_command(
+    input: String,
+    db: DbArgs,
+    dry_run: bool,
+    json_out: Option<String>,
+) -> Result<()> {
+    println!("🌱 Seeding database...");
 
-    let pb = ProgressBar::new(addresses.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("█▓▒░  "),
-    );
+    if !Path::new(&input).exists() {
+        println!("⚠️  No {} found, skipping contract import", input);
+        return Ok(());
+    }
 
-    let mut batch = Vec::new();
-    let mut total_imported = 0;
-
-    for addr in addresses {
-        pb.set_message(format!("Fetching {}", addr.address));
-
-        match client
-            .fetch_contract(&addr.address, addr.chain_id, addr.protocol)
-            .await
-        {
-            Ok(contract) => {
-                batch.push(contract);
-
-                // Import batch when it reaches the specified size
-                if batch.len() >= batch_size {
-                    let imported = db_importer::import_contracts_to_db(&batch, &database_url).await?;
-                    total_imported += imported;
-                    pb.println(format!("💾 Imported batch of {} contracts", imported));
-                    batch.clear();
-                }
-            }
-            Err(e) => {
-                pb.println(format!("✗ {} - Error: {}", addr.address, e));
-            }
-        }
+    let importer = db.connect().await?;
+    println!("   📊 Existing contracts: {}", importer.count_contracts().await?);
 
-        pb.inc(1);
+    if dry_run {
+        let preview = importer.preview_seed(&input).await?;
+        importer.close().await;
+        return report_preview(&preview, json_out.as_deref());
     }
 
-    // Import remaining contracts
-    if !batch.is_empty() {
-        let imported = db_importer::import_contracts_to_db(&batch, &database_url).await?;
-        total_imported += imported;
-        pb.println(format!("💾 Imported final batch of {} contracts", imported));
+    println!("   📥 Importing from {}...", input);
+    let summary = importer.seed(&input).await?;
+    println!("   ✓ {}", summary);
+
+    println!("   📊 Contracts after seed: {}", importer.count_contracts().await?);
+    importer.close().await;
+
+    println!("✅ Seeding complete!");
+    Ok(())
+}
+
+fn report_preview(preview: &merge_preview::MergePreview, json_out: Option<&str>) -> Result<()> {
+    preview.print();
+    if let Some(path) = json_out {
+        preview.write_json(path)?;
+        println!("\n📝 Preview written to {}", path);
     }
+    Ok(())
+}
 
-    pb.finish_with_message("Done!");
+async fn export_command(
+    output: String,
+    format: Option<exporter::ExportFormat>,
+    filter: exporter::ExportFilter,
+    database_url: Option<String>,
+) -> Result<()> {
+    let format = format.unwrap_or_else(|| {
+        match Path::new(&output).extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl" | "ndjson") => exporter::ExportFormat::Jsonl,
+            _ => exporter::ExportFormat::Csv,
+        }
+    });
+
+    let database_url = resolve_database_url(database_url);
+    let pool = sqlx::postgres::PgPoolOptions::new()
+        .max_connections(1)
+        .connect(&database_url)
+        .await?;
 
-    println!("\n✅ Success! Imported {} contracts to database", total_imported);
+    println!("📤 Exporting contracts to: {}", output);
+    let written = exporter::export_contracts(&pool, &filter, format, &output).await?;
+    pool.close().await;
 
+    println!("✅ Exported {} contracts", written);
     Ok(())
 }
 
-async fn import_command(input: String, database_url: Option<String>) -> Result<()> {
-    let database_url = database_url
-        .or_else(|| std::env::var("DATABASE_URL").ok())
-        .expect("DATABASE_URL must be provided via --database-url or environment variable");
+/// Parse a request rate, which must be a positive number
+fn parse_rps(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(rps) if rps.is_finite() && rps > 0.0 => Ok(rps),
+        _ => Err(format!(
+            "expected a number of requests per second above 0, got {:?}",
+            value
+        )),
+    }
+}
 
-    println!("📖 Reading contracts from: {}", input);
-    let contracts = csv_handler::read_contracts_from_csv(&input)?;
+/// Parse unix seconds or a `YYYY-MM-DD` date (midnight UTC)
+fn parse_timestamp(value: &str) -> Result<i64, String> {
+    if let Ok(seconds) = value.parse() {
+        return Ok(seconds);
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp())
+        .map_err(|_| format!("expected unix seconds or YYYY-MM-DD, got {:?}", value))
+}
 
-    println!("✓ Found {} contracts in CSV", contracts.len());
-    println!("💾 Importing to database...\n");
+fn validate_input_command(inputs: Vec<String>, input_format: Option<InputFormat>) -> Result<()> {
+    let mut rejected = 0;
 
-    let imported = db_importer::import_contracts_to_db(&contracts, &database_url).await?;
+    for input in &inputs {
+        let format = input_format.unwrap_or_else(|| {
+            match Path::new(input).extension().and_then(|ext| ext.to_str()) {
+                Some("csv") => InputFormat::MetadataCsv,
+                _ => InputFormat::Curated,
+            }
+        });
 
-    println!("\n✅ Success! Imported {} contracts to database", imported);
+        let (accepted, rejections) = match format {
+            InputFormat::Curated => {
+                let validated = validation::curated_file(input)?;
+                (validated.rows.len(), validated.rejected)
+            }
+            InputFormat::MetadataCsv => {
+                let validated = validation::metadata_csv(input)?;
+                (validated.rows.len(), validated.rejected)
+            }
+            InputFormat::Json | InputFormat::Db => {
+                anyhow::bail!("validate-input only checks curated files and metadata CSVs")
+            }
+        };
+
+        if rejections.is_empty() {
+            println!("✅ {}: {} addresses", input, accepted);
+        } else {
+            println!("❌ {}: {} addresses, {} invalid lines", input, accepted, rejections.len());
+            for rejection in &rejections {
+                println!("   {}", rejection);
+            }
+        }
+        rejected += rejections.len();
+    }
 
+    if rejected > 0 {
+        anyhow::bail!("{} invalid lines found", rejected);
+    }
     Ok(())
 }
 
+fn chains_list_command() {
+    println!("🔗 Registered chains:\n");
+    println!("          ID  NAME         SYMBOL  BLOCK  ALIASES");
+    for chain in chains::registry().iter() {
+        println!(
+            "  {:>10}  {:<12} {:<6} {:>5}s  {}",
+            chain.chain_id,
+            chain.name,
+            chain.native_symbol,
+            chain.block_time_secs,
+            chain.aliases.join(", ")
+        );
+    }
+}
+
 fn stats_command(input: String) -> Result<()> {
     println!("📊 Reading statistics from: {}", input);
-    let contracts = csv_handler::read_contracts_from_csv(&input)?;
+    let mut total = 0;
+    let mut with_symbol = 0;
+    let mut proxies = 0;
+    let mut with_protocol = 0;
+    let mut protocols: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut chains: std::collections::HashMap<i32, usize> = std::collections::HashMap::new();
 
-    let total = contracts.len();
-    let with_symbol = contracts.iter().filter(|c| c.symbol.is_some()).count();
-    let proxies = contracts.iter().filter(|c| c.is_proxy).count();
-    let with_protocol = contracts.iter().filter(|c| c.protocol.is_some()).count();
+    for contract in csv_handler::read_contracts_from_csv(&input)? {
+        let contract = contract?;
+        total += 1;
+        with_symbol += contract.symbol.is_some() as usize;
+        proxies += contract.is_proxy as usize;
+        if let Some(proto) = contract.protocol {
+            with_protocol += 1;
+            *protocols.entry(proto).or_insert(0) += 1;
+        }
+        *chains.entry(contract.chain_id).or_insert(0) += 1;
+    }
 
     println!("\n📈 Contract Statistics:");
     println!("  Total contracts: {}", total);
@@ -288,14 +883,6 @@ fn stats_command(input: String) -> Result<()> {
     println!("  Proxies:         {}", proxies);
     println!("  With protocol:   {}", with_protocol);
 
-    // Group by protocol
-    let mut protocols: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
-    for contract in &contracts {
-        if let Some(ref proto) = contract.protocol {
-            *protocols.entry(proto.clone()).or_insert(0) += 1;
-        }
-    }
-
     if !protocols.is_empty() {
         println!("\n📦 By Protocol:");
         let mut sorted: Vec<_> = protocols.iter().collect();
@@ -305,17 +892,11 @@ fn stats_command(input: String) -> Result<()> {
         }
     }
 
-    // Group by chain
-    let mut chains: std::collections::HashMap<i32, usize> = std::collections::HashMap::new();
-    for contract in &contracts {
-        *chains.entry(contract.chain_id).or_insert(0) += 1;
-    }
-
     println!("\n🔗 By Chain:");
     let mut sorted: Vec<_> = chains.iter().collect();
     sorted.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
     for (chain_id, count) in sorted {
-        println!("  {} ({}): {}", models::chain_id_to_name(*chain_id), chain_id, count);
+        println!("  {} ({}): {}", chains::registry().name(*chain_id), chain_id, count);
     }
 
     Ok(())
@@ -323,92 +904,151 @@ fn stats_command(input: String) -> Result<()> {
 
 async fn fetch_from_metadata_csv_command(
     input: String,
-    api_key: Option<String>,
-    database_url: Option<String>,
+    fetch: FetchArgs,
+    db: DbArgs,
     batch_size: usize,
 ) -> Result<()> {
-    let api_key = api_key
-        .or_else(|| std::env::var("ETHERSCAN_API_KEY").ok())
-        .expect("ETHERSCAN_API_KEY must be provided via --api-key or environment variable");
-
-    let database_url = database_url
-        .or_else(|| std::env::var("DATABASE_URL").ok())
-        .expect("DATABASE_URL must be provided via --database-url or environment variable");
+    let source = address_source::MetadataCsvSource { path: input.clone() };
+    let importer = db.connect().await?;
+    let sink = contract_sink::PostgresSink::new(importer.clone(), batch_size);
 
-    println!("📖 Reading metadata CSV from: {}", input);
+    let summary = fetch.pipeline(&input)?.run(&source, vec![Box::new(sink)]).await?;
+    importer.close().await;
 
-    // Read CSV file
-    let mut rdr = csv::Reader::from_path(&input)?;
-    let mut addresses = Vec::new();
-
-    for result in rdr.records() {
-        let record = result?;
-
-        // CSV format: address,chain,chain_id,name,symbol,is_proxy,implementation_address,protocol,contract_type,version,created_at,updated_at
-        let address = record.get(0).unwrap_or("").to_string();
-        let chain_id: i32 = record.get(2).unwrap_or("1").parse().unwrap_or(1);
-        let protocol = record.get(7).map(|s| s.to_string());
+    print_summaries(&summary)
+}
 
-        if !address.is_empty() && address.starts_with("0x") {
-            addresses.push(CuratedAddress {
-                address,
-                chain_id,
-                protocol,
-            });
+async fn retry_failures_command(
+    input: String,
+    kinds: Vec<String>,
+    output: Option<String>,
+    fetch: FetchArgs,
+    db: DbArgs,
+    batch_size: usize,
+) -> Result<()> {
+    let source = address_source::FailureReportSource {
+        path: input.clone(),
+        kinds,
+    };
+
+    let (sinks, importer) = match output {
+        // Retried contracts belong with the ones the original run already wrote
+        Some(output) => (file_sinks(&[output], true), None),
+        None => {
+            let importer = db.connect().await?;
+            let sink = contract_sink::PostgresSink::new(importer.clone(), batch_size);
+            (vec![Box::new(sink) as Box<dyn ContractSink>], Some(importer))
+        }
+    };
+
+    let mut pipeline = fetch.pipeline(&input)?;
+    // Failures this run doesn't retry stay in a report that replaces the input
+    if let Some(report) = pipeline.failures.as_mut() {
+        if Path::new(report.path()) == Path::new(&input) && !source.kinds.is_empty() {
+            for record in failures::read_failures(&input)? {
+                if !source.kinds.contains(&record.error_kind) {
+                    report.carry_over(&record)?;
+                }
+            }
         }
     }
 
-    println!("✓ Found {} addresses to fetch", addresses.len());
-    println!("💾 Fetching from Etherscan and importing to database...\n");
+    let summary = pipeline.run(&source, sinks).await?;
 
-    let client = etherscan::EtherscanClient::new(api_key);
+    if let Some(importer) = importer {
+        importer.close().await;
+    }
 
-    let pb = ProgressBar::new(addresses.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("█▓▒░  "),
-    );
+    print_summaries(&summary)
+}
 
-    let mut batch = Vec::new();
-    let mut total_imported = 0;
-
-    for addr in addresses {
-        pb.set_message(format!("Fetching {}", addr.address));
-
-        match client
-            .fetch_contract(&addr.address, addr.chain_id, addr.protocol)
-            .await
-        {
-            Ok(contract) => {
-                pb.println(format!("✓ Imported: {} ({})", contract.name, contract.address));
-                batch.push(contract);
-
-                // Import batch when it reaches the specified size
-                if batch.len() >= batch_size {
-                    let imported = db_importer::import_contracts_to_db(&batch, &database_url).await?;
-                    total_imported += imported;
-                    batch.clear();
-                }
+async fn migrate_command(
+    action: Option<MigrateAction>,
+    dry_run: bool,
+    source: String,
+    database_url: Option<String>,
+) -> Result<()> {
+    use sqlx::Connection;
+
+    let database_url = resolve_database_url(database_url);
+    let migrator = migrator::Migrator::new(Path::new(&source))?;
+    let mut conn = sqlx::PgConnection::connect(&database_url).await?;
+
+    match action {
+        Some(MigrateAction::Status) => {
+            println!("🗄️  Migrations in {}:\n", source);
+            for row in migrator.status(&mut conn).await? {
+                let applied = match (&row.installed_on, row.execution_time) {
+                    (Some(on), Some(nanos)) => {
+                        format!("{} ({:.1}ms)", on, nanos as f64 / 1_000_000.0)
+                    }
+                    _ => String::new(),
+                };
+                println!(
+                    "  {:>3}  {:<10}  {:<32}  {:<4}  {}",
+                    row.version,
+                    row.state.as_str(),
+                    row.description,
+                    if row.reversible { "down" } else { "" },
+                    applied
+                );
+            }
+        }
+        Some(MigrateAction::Rollback { to }) => {
+            println!("🗄️  Rolling back to version {}...", to);
+            let reverted = migrator.rollback(&mut conn, to, dry_run).await?;
+            if dry_run {
+                println!("\n🔍 Dry run: {} migrations would be reverted", reverted);
+            } else {
+                println!("\n✅ Rollback complete! Reverted {}", reverted);
             }
-            Err(e) => {
-                pb.println(format!("✗ {} - Error: {}", addr.address, e));
+        }
+        None => {
+            println!(
+                "🗄️  {} migrations...",
+                if dry_run { "Checking" } else { "Running" }
+            );
+            let applied = migrator.run(&mut conn, dry_run).await?;
+            if dry_run {
+                println!("\n🔍 Dry run: {} migrations would be applied", applied);
+            } else {
+                println!("\n✅ Migrations complete! Applied {}", applied);
             }
         }
-
-        pb.inc(1);
     }
 
-    // Import remaining contracts
-    if !batch.is_empty() {
-        let imported = db_importer::import_contracts_to_db(&batch, &database_url).await?;
-        total_imported += imported;
-    }
+    conn.close().await?;
+    Ok(())
+}
+
+async fn schema_diff_command(
+    source: String,
+    database_url: Option<String>,
+    scratch_url: Option<String>,
+) -> Result<()> {
+    use sqlx::Connection;
 
-    pb.finish_with_message("Done!");
+    let database_url = resolve_database_url(database_url);
+    let migrator = migrator::Migrator::new(Path::new(&source))?;
 
-    println!("\n✅ Success! Imported {} contracts to database", total_imported);
+    println!("🧪 Applying {} to a scratch database...", source);
+    let expected =
+        schema::expected_schema(&migrator, &database_url, scratch_url.as_deref()).await?;
 
-    Ok(())
+    println!("🔍 Inspecting live database...");
+    let mut conn = sqlx::PgConnection::connect(&database_url).await?;
+    let actual = schema::Schema::introspect(&mut conn).await?;
+    conn.close().await?;
+
+    let drift = schema::diff(&expected, &actual);
+    if drift.is_empty() {
+        println!("\n✅ Schema matches {} ({} tables)", source, expected.tables.len());
+        return Ok(());
+    }
+
+    println!();
+    for difference in &drift {
+        println!("  {}", difference);
+    }
+    anyhow::bail!("Schema drift detected: {} differences", drift.len())
 }
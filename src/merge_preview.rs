@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+
+/// Longest old/new value kept for a changed column
+pub const PREVIEW_VALUE_CHARS: usize = 80;
+
+/// Updated contracts listed in the printed summary; the JSON report has all of them
+const LISTED_UPDATES: usize = 25;
+
+#[derive(Debug, Serialize)]
+pub struct ContractRef {
+    pub chain_id: i32,
+    pub address: String,
+}
+
+/// One column an update would change. Values are left out for `source_code`
+/// and `abi`, which are too large to show.
+#[derive(Debug, Serialize)]
+pub struct ColumnChange {
+    pub column: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl ColumnChange {
+    fn describe(&self) -> String {
+        match (&self.old, &self.new) {
+            (None, None) => format!("{} changed", self.column),
+            (old, new) => format!(
+                "{}: {} → {}",
+                self.column,
+                old.as_deref().unwrap_or("∅"),
+                new.as_deref().unwrap_or("∅")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContractChange {
+    pub chain_id: i32,
+    pub address: String,
+    pub changes: Vec<ColumnChange>,
+}
+
+/// What a merge would do, computed by `--dry-run`
+#[derive(Debug, Default, Serialize)]
+pub struct MergePreview {
+    pub inserted: Vec<ContractRef>,
+    pub updated: Vec<ContractChange>,
+    pub unchanged: u64,
+}
+
+impl MergePreview {
+    /// How many updates touch each column
+    pub fn column_counts(&self) -> BTreeMap<&'static str, usize> {
+        let mut counts = BTreeMap::new();
+        for contract in &self.updated {
+            for change in &contract.changes {
+                *counts.entry(change.column).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    pub fn print(&self) {
+        println!("\n🔍 Dry run: nothing was written");
+        println!("   ➕ Would insert:  {}", self.inserted.len());
+        println!("   ✏️  Would update:  {}", self.updated.len());
+        println!("   ✓ Unchanged:     {}", self.unchanged);
+
+        let counts = self.column_counts();
+        if !counts.is_empty() {
+            println!("\n📋 Changed columns:");
+            for (column, count) in &counts {
+                println!("   {}: {}", column, count);
+            }
+        }
+
+        if !self.updated.is_empty() {
+            println!("\n✏️  Updates:");
+            for contract in self.updated.iter().take(LISTED_UPDATES) {
+                let changes: Vec<String> = contract
+                    .changes
+                    .iter()
+                    .map(ColumnChange::describe)
+                    .collect();
+                println!(
+                    "   {} (chain {}): {}",
+                    contract.address,
+                    contract.chain_id,
+                    changes.join("; ")
+                );
+            }
+            if self.updated.len() > LISTED_UPDATES {
+                println!("   ... and {} more", self.updated.len() - LISTED_UPDATES);
+            }
+        }
+    }
+
+    /// Write the preview as JSON, with a summary block for PR comments
+    pub fn write_json(&self, path: &str) -> Result<()> {
+        let report = serde_json::json!({
+            "summary": {
+                "inserted": self.inserted.len(),
+                "updated": self.updated.len(),
+                "unchanged": self.unchanged,
+                "changed_columns": self.column_counts(),
+            },
+            "inserted": self.inserted,
+            "updated": self.updated,
+        });
+
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path))?;
+        serde_json::to_writer_pretty(file, &report)?;
+        Ok(())
+    }
+}
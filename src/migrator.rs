@@ -0,0 +1,598 @@
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha384};
+use sqlx::{Connection, Executor, PgConnection};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Checksum written by the hand-inserted row of the original `001_initial_schema.sql`
+const PLACEHOLDER_CHECKSUM: &[u8] = &[0];
+
+/// Key for `pg_advisory_lock`, so two deploys never migrate at the same time
+const MIGRATION_LOCK_ID: i64 = 0x6462_6d61_7374_6572; // "dbmaster"
+
+/// Same layout as the table maintained by `sqlx migrate`
+const CREATE_MIGRATIONS_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS _sqlx_migrations (
+    version BIGINT PRIMARY KEY,
+    description TEXT NOT NULL,
+    installed_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    success BOOLEAN NOT NULL,
+    checksum BYTEA NOT NULL,
+    execution_time BIGINT NOT NULL
+)
+"#;
+
+/// A `NNN_description.sql` file from the migrations directory, with its
+/// `NNN_description.down.sql` counterpart if there is one
+pub struct Migration {
+    pub version: i64,
+    pub description: String,
+    pub path: PathBuf,
+    pub sql: String,
+    /// SHA-384 of the file, as computed by sqlx
+    pub checksum: Vec<u8>,
+    /// SQL that reverts this migration
+    pub down_sql: Option<String>,
+}
+
+impl Migration {
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// A row of `_sqlx_migrations`
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub installed_on: String,
+    pub success: bool,
+    pub checksum: Vec<u8>,
+    /// Nanoseconds
+    pub execution_time: i64,
+}
+
+/// How a migration file relates to the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but the file has been edited since
+    Modified,
+    /// Applied by `scripts/migrate.sh` with the placeholder checksum
+    Unverified,
+    /// Applied, but the file no longer exists
+    Missing,
+    /// Recorded with `success = false`
+    Failed,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Modified => "modified",
+            MigrationState::Unverified => "unverified",
+            MigrationState::Missing => "missing",
+            MigrationState::Failed => "failed",
+        }
+    }
+}
+
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    pub installed_on: Option<String>,
+    pub execution_time: Option<i64>,
+    /// Whether a down migration exists
+    pub reversible: bool,
+}
+
+/// Read and sort the migration files of `dir`, pairing each with its down file
+pub fn load_migrations(dir: &Path) -> Result<Vec<Migration>> {
+    let mut migrations = BTreeMap::new();
+    let mut down_files = BTreeMap::new();
+
+    let entries = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read migrations directory {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(stem) = file_name.strip_suffix(".sql") else {
+            continue;
+        };
+
+        let Some((version, description)) = stem.split_once('_') else {
+            bail!("Migration {} is not named NNN_description.sql", file_name);
+        };
+        let version: i64 = version
+            .parse()
+            .with_context(|| format!("Migration {} has no numeric version", file_name))?;
+
+        let sql = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+
+        if description.ends_with(".down") {
+            if down_files.insert(version, sql).is_some() {
+                bail!("Two down migrations share version {}", version);
+            }
+            continue;
+        }
+
+        let migration = Migration {
+            version,
+            description: description.to_string(),
+            checksum: Sha384::digest(sql.as_bytes()).to_vec(),
+            path,
+            sql,
+            down_sql: None,
+        };
+
+        if let Some(other) = migrations.insert(version, migration) {
+            bail!(
+                "Two migrations share version {}: {}",
+                version,
+                other.file_name()
+            );
+        }
+    }
+
+    for (version, down_sql) in down_files {
+        let Some(migration) = migrations.get_mut(&version) else {
+            bail!("Down migration for version {} has no up migration", version);
+        };
+        migration.down_sql = Some(down_sql);
+    }
+
+    Ok(migrations.into_values().collect())
+}
+
+/// Applies the migrations directory to one database connection
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    pub fn new(dir: &Path) -> Result<Self> {
+        Ok(Self {
+            migrations: load_migrations(dir)?,
+        })
+    }
+
+    /// Every migration file and every recorded version, in version order
+    pub async fn status(&self, conn: &mut PgConnection) -> Result<Vec<MigrationStatus>> {
+        let applied = applied_migrations(conn).await?;
+        let mut rows = Vec::new();
+
+        for migration in &self.migrations {
+            let recorded = applied.get(&migration.version);
+            rows.push(MigrationStatus {
+                version: migration.version,
+                description: migration.description.clone(),
+                state: match recorded {
+                    None => MigrationState::Pending,
+                    Some(row) => compare(migration, row),
+                },
+                installed_on: recorded.map(|row| row.installed_on.clone()),
+                execution_time: recorded.map(|row| row.execution_time),
+                reversible: migration.down_sql.is_some(),
+            });
+        }
+
+        for row in applied.values() {
+            if !self.migrations.iter().any(|m| m.version == row.version) {
+                rows.push(MigrationStatus {
+                    version: row.version,
+                    description: row.description.clone(),
+                    state: MigrationState::Missing,
+                    installed_on: Some(row.installed_on.clone()),
+                    execution_time: Some(row.execution_time),
+                    reversible: false,
+                });
+            }
+        }
+
+        rows.sort_by_key(|row| row.version);
+        Ok(rows)
+    }
+
+    /// Apply every pending migration in version order, one transaction each.
+    /// Returns how many were applied (or would be, with `dry_run`).
+    pub async fn run(&self, conn: &mut PgConnection, dry_run: bool) -> Result<usize> {
+        if dry_run {
+            return self.apply_pending(conn, true).await;
+        }
+
+        conn.execute(CREATE_MIGRATIONS_TABLE).await?;
+        lock(conn).await?;
+        let result = self.apply_pending(conn, false).await;
+        unlock(conn).await?;
+
+        result
+    }
+
+    /// Revert every applied migration above `target`, newest first, one
+    /// transaction each. Returns how many were reverted (or would be).
+    pub async fn rollback(
+        &self,
+        conn: &mut PgConnection,
+        target: i64,
+        dry_run: bool,
+    ) -> Result<usize> {
+        if dry_run {
+            return self.revert_to(conn, target, true).await;
+        }
+
+        lock(conn).await?;
+        let result = self.revert_to(conn, target, false).await;
+        unlock(conn).await?;
+
+        result
+    }
+
+    async fn apply_pending(&self, conn: &mut PgConnection, dry_run: bool) -> Result<usize> {
+        let applied = applied_migrations(conn).await?;
+        self.verify_applied(conn, &applied, dry_run).await?;
+
+        let pending: Vec<&Migration> = self
+            .migrations
+            .iter()
+            .filter(|m| !applied.contains_key(&m.version))
+            .collect();
+
+        if pending.is_empty() {
+            println!("   ✓ Database is up to date");
+            return Ok(0);
+        }
+
+        for migration in &pending {
+            if dry_run {
+                println!("   📄 Would apply: {}", migration.file_name());
+                continue;
+            }
+
+            println!("   📄 Applying: {}", migration.file_name());
+            let started = Instant::now();
+
+            let mut tx = conn.begin().await?;
+            tx.execute(migration.sql.as_str())
+                .await
+                .with_context(|| format!("Migration {} failed", migration.file_name()))?;
+            let execution_time = started.elapsed().as_nanos() as i64;
+
+            sqlx::query(
+                r#"
+                INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+                VALUES ($1, $2, true, $3, $4)
+                "#,
+            )
+            .bind(migration.version)
+            .bind(&migration.description)
+            .bind(&migration.checksum)
+            .bind(execution_time)
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+            println!(
+                "      ✓ Applied in {:.1}ms",
+                execution_time as f64 / 1_000_000.0
+            );
+        }
+
+        Ok(pending.len())
+    }
+
+    async fn revert_to(
+        &self,
+        conn: &mut PgConnection,
+        target: i64,
+        dry_run: bool,
+    ) -> Result<usize> {
+        let applied = applied_migrations(conn).await?;
+        self.verify_applied(conn, &applied, dry_run).await?;
+
+        // verify_applied guarantees every applied version still has its file
+        let to_revert: Vec<&Migration> = self
+            .migrations
+            .iter()
+            .rev()
+            .filter(|m| m.version > target && applied.contains_key(&m.version))
+            .collect();
+
+        if to_revert.is_empty() {
+            println!("   ✓ Nothing applied above version {}", target);
+            return Ok(0);
+        }
+
+        let irreversible: Vec<String> = to_revert
+            .iter()
+            .filter(|m| m.down_sql.is_none())
+            .map(|m| m.file_name())
+            .collect();
+        if !irreversible.is_empty() {
+            bail!(
+                "Refusing to roll back: no .down.sql for {}",
+                irreversible.join(", ")
+            );
+        }
+
+        for migration in &to_revert {
+            if dry_run {
+                println!("   ↩️  Would revert: {}", migration.file_name());
+                continue;
+            }
+
+            println!("   ↩️  Reverting: {}", migration.file_name());
+            let started = Instant::now();
+
+            let mut tx = conn.begin().await?;
+            tx.execute(migration.down_sql.as_deref().unwrap_or_default())
+                .await
+                .with_context(|| format!("Reverting {} failed", migration.file_name()))?;
+            sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+                .bind(migration.version)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            println!(
+                "      ✓ Reverted in {:.1}ms",
+                started.elapsed().as_secs_f64() * 1000.0
+            );
+        }
+
+        Ok(to_revert.len())
+    }
+
+    /// Refuse to continue if an applied migration was edited, removed or failed.
+    /// Versions applied with the placeholder checksum get their real one recorded.
+    async fn verify_applied(
+        &self,
+        conn: &mut PgConnection,
+        applied: &BTreeMap<i64, AppliedMigration>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut problems = Vec::new();
+
+        for row in applied.values() {
+            let Some(migration) = self.migrations.iter().find(|m| m.version == row.version) else {
+                problems.push(format!(
+                    "version {} ({}) is applied but its file is missing",
+                    row.version, row.description
+                ));
+                continue;
+            };
+
+            match compare(migration, row) {
+                MigrationState::Modified => problems.push(format!(
+                    "{} was edited after it was applied",
+                    migration.file_name()
+                )),
+                MigrationState::Failed => {
+                    problems.push(format!("{} is recorded as failed", migration.file_name()))
+                }
+                MigrationState::Unverified if dry_run => {
+                    println!("   🔏 Would record checksum of {}", migration.file_name())
+                }
+                MigrationState::Unverified => {
+                    sqlx::query("UPDATE _sqlx_migrations SET checksum = $2 WHERE version = $1")
+                        .bind(migration.version)
+                        .bind(&migration.checksum)
+                        .execute(&mut *conn)
+                        .await?;
+                    println!("   🔏 Recorded checksum of {}", migration.file_name());
+                }
+                _ => {}
+            }
+        }
+
+        if !problems.is_empty() {
+            bail!(
+                "Refusing to migrate:\n  - {}\nAdd a new migration instead of editing applied ones",
+                problems.join("\n  - ")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+async fn lock(conn: &mut PgConnection) -> Result<()> {
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+async fn unlock(conn: &mut PgConnection) -> Result<()> {
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+fn compare(migration: &Migration, row: &AppliedMigration) -> MigrationState {
+    if !row.success {
+        MigrationState::Failed
+    } else if row.checksum == PLACEHOLDER_CHECKSUM {
+        MigrationState::Unverified
+    } else if row.checksum != migration.checksum {
+        MigrationState::Modified
+    } else {
+        MigrationState::Applied
+    }
+}
+
+/// Rows of `_sqlx_migrations` by version; empty if the table does not exist yet
+pub async fn applied_migrations(
+    conn: &mut PgConnection,
+) -> Result<BTreeMap<i64, AppliedMigration>> {
+    let (exists,): (bool,) = sqlx::query_as("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(&mut *conn)
+        .await?;
+    if !exists {
+        return Ok(BTreeMap::new());
+    }
+
+    let rows: Vec<(i64, String, String, bool, Vec<u8>, i64)> = sqlx::query_as(
+        r#"
+        SELECT version, description, to_char(installed_on, 'YYYY-MM-DD HH24:MI:SS'),
+               success, checksum, execution_time
+        FROM _sqlx_migrations
+        ORDER BY version
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(version, description, installed_on, success, checksum, execution_time)| {
+                (
+                    version,
+                    AppliedMigration {
+                        version,
+                        description,
+                        installed_on,
+                        success,
+                        checksum,
+                        execution_time,
+                    },
+                )
+            },
+        )
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(files: &[(&str, &str)]) -> Result<Vec<Migration>> {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, sql) in files {
+            fs::write(dir.path().join(name), sql).unwrap();
+        }
+        load_migrations(dir.path())
+    }
+
+    fn load_err(files: &[(&str, &str)]) -> String {
+        load(files).err().expect("expected an error").to_string()
+    }
+
+    #[test]
+    fn pairs_down_files_and_sorts_by_version() {
+        let migrations = load(&[
+            ("002_add_sources.sql", "CREATE TABLE sources ();"),
+            ("002_add_sources.down.sql", "DROP TABLE sources;"),
+            ("001_initial_schema.sql", "CREATE TABLE contracts ();"),
+            ("README.md", "not a migration"),
+        ])
+        .unwrap();
+
+        assert_eq!(migrations.len(), 2);
+        assert_eq!(migrations[0].version, 1);
+        assert_eq!(migrations[0].description, "initial_schema");
+        assert_eq!(migrations[0].down_sql, None);
+        assert_eq!(migrations[1].version, 2);
+        assert_eq!(migrations[1].description, "add_sources");
+        assert_eq!(migrations[1].file_name(), "002_add_sources.sql");
+        assert_eq!(
+            migrations[1].down_sql.as_deref(),
+            Some("DROP TABLE sources;")
+        );
+        assert_eq!(
+            migrations[1].checksum,
+            Sha384::digest(b"CREATE TABLE sources ();").to_vec()
+        );
+    }
+
+    #[test]
+    fn rejects_orphan_down_file() {
+        let err = load_err(&[
+            ("001_initial_schema.sql", ""),
+            ("002_add_sources.down.sql", ""),
+        ]);
+        assert!(err.contains("version 2 has no up migration"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_duplicate_versions() {
+        let err = load_err(&[("001_initial_schema.sql", ""), ("001_other.sql", "")]);
+        assert!(err.contains("Two migrations share version 1"), "{}", err);
+
+        let err = load_err(&[
+            ("001_initial_schema.sql", ""),
+            ("001_initial_schema.down.sql", ""),
+            ("001_other.down.sql", ""),
+        ]);
+        assert!(
+            err.contains("Two down migrations share version 1"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_badly_named_files() {
+        let err = load_err(&[("initial.sql", "")]);
+        assert!(err.contains("not named NNN_description.sql"), "{}", err);
+
+        let err = load_err(&[("first_initial.sql", "")]);
+        assert!(err.contains("no numeric version"), "{}", err);
+    }
+
+    fn applied(success: bool, checksum: &[u8]) -> AppliedMigration {
+        AppliedMigration {
+            version: 1,
+            description: "initial_schema".to_string(),
+            installed_on: "2024-01-01 00:00:00".to_string(),
+            success,
+            checksum: checksum.to_vec(),
+            execution_time: 0,
+        }
+    }
+
+    #[test]
+    fn compares_file_with_recorded_row() {
+        let migration = load(&[("001_initial_schema.sql", "CREATE TABLE contracts ();")])
+            .unwrap()
+            .remove(0);
+        let checksum = migration.checksum.clone();
+
+        assert_eq!(
+            compare(&migration, &applied(true, &checksum)),
+            MigrationState::Applied
+        );
+        assert_eq!(
+            compare(&migration, &applied(true, PLACEHOLDER_CHECKSUM)),
+            MigrationState::Unverified
+        );
+        assert_eq!(
+            compare(&migration, &applied(true, &[1, 2, 3])),
+            MigrationState::Modified
+        );
+        // A failed run is reported as such whatever its checksum
+        assert_eq!(
+            compare(&migration, &applied(false, &checksum)),
+            MigrationState::Failed
+        );
+        assert_eq!(
+            compare(&migration, &applied(false, PLACEHOLDER_CHECKSUM)),
+            MigrationState::Failed
+        );
+    }
+}
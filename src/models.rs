@@ -1,8 +1,92 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha3::{Digest, Keccak256};
+use std::fmt;
+use std::str::FromStr;
+
+/// A 20-byte account address, always held in lowercase
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address(String);
+
+impl Address {
+    /// Parse `0x` followed by 40 hex digits. Mixed-case input must carry a
+    /// valid EIP-55 checksum; all-lowercase or all-uppercase is accepted as is.
+    pub fn parse(address: &str) -> Result<Self, String> {
+        let hex = address
+            .strip_prefix("0x")
+            .or_else(|| address.strip_prefix("0X"))
+            .ok_or_else(|| format!("address {:?} does not start with 0x", address))?;
+        if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("address {:?} is not 20 bytes of hex", address));
+        }
+
+        let parsed = Address(format!("0x{}", hex.to_lowercase()));
+        let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase())
+            && hex.chars().any(|c| c.is_ascii_uppercase());
+        if mixed_case && parsed.checksummed()[2..] != *hex {
+            return Err(format!(
+                "address {:?} has an invalid EIP-55 checksum (expected {})",
+                address,
+                parsed.checksummed()
+            ));
+        }
+
+        Ok(parsed)
+    }
+
+    /// The lowercase form stored in CSVs and the database
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// EIP-55 mixed-case rendering, for display
+    pub fn checksummed(&self) -> String {
+        let hex = &self.0[2..];
+        let hash = Keccak256::digest(hex.as_bytes());
+
+        let mut checksummed = String::with_capacity(42);
+        checksummed.push_str("0x");
+        for (i, c) in hex.chars().enumerate() {
+            let nibble = (hash[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 {
+                checksummed.push(c.to_ascii_uppercase());
+            } else {
+                checksummed.push(c);
+            }
+        }
+        checksummed
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Address::parse(s.trim())
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContractData {
-    pub address: String,
+    pub address: Address,
     pub chain: String,
     pub chain_id: i32,
     pub name: String,
@@ -10,24 +94,49 @@ pub struct ContractData {
     pub source_code: String,
     pub abi: String,
     pub is_proxy: bool,
-    pub implementation_address: Option<String>,
+    pub implementation_address: Option<Address>,
     pub protocol: Option<String>,
     pub contract_type: Option<String>,
     pub version: Option<String>,
+    /// Unix time the data was fetched; decides `--on-conflict newer-wins`
+    #[serde(default)]
+    pub updated_at: Option<i64>,
+    /// Compiler and verification metadata reported by Etherscan
+    #[serde(default)]
+    pub compiler_version: Option<String>,
+    #[serde(default)]
+    pub optimization_used: Option<bool>,
+    #[serde(default)]
+    pub optimization_runs: Option<i32>,
+    #[serde(default)]
+    pub evm_version: Option<String>,
+    #[serde(default)]
+    pub constructor_arguments: Option<String>,
+    #[serde(default)]
+    pub library: Option<String>,
+    #[serde(default)]
+    pub license_type: Option<String>,
+    #[serde(default)]
+    pub swarm_source: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContractMetadata {
-    pub address: String,
+    pub address: Address,
     pub chain: String,
     pub chain_id: i32,
     pub name: String,
     pub symbol: Option<String>,
+    #[serde(deserialize_with = "deserialize_pg_bool")]
     pub is_proxy: bool,
-    pub implementation_address: Option<String>,
+    pub implementation_address: Option<Address>,
     pub protocol: Option<String>,
     pub contract_type: Option<String>,
     pub version: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    #[serde(default)]
+    pub updated_at: Option<i64>,
 }
 
 impl From<ContractData> for ContractMetadata {
@@ -43,7 +152,42 @@ impl From<ContractData> for ContractMetadata {
             protocol: contract.protocol,
             contract_type: contract.contract_type,
             version: contract.version,
+            created_at: None,
+            updated_at: contract.updated_at,
+        }
+    }
+}
+
+impl ContractMetadata {
+    /// Overlay the known metadata on a freshly fetched contract; curated values
+    /// win over what was derived from Etherscan, except the implementation
+    /// address, where the freshly resolved one is more current
+    pub fn apply_to(&self, mut contract: ContractData) -> ContractData {
+        if !self.name.is_empty() {
+            contract.name = self.name.clone();
         }
+        contract.symbol = self.symbol.clone().or(contract.symbol);
+        contract.is_proxy |= self.is_proxy;
+        contract.implementation_address = contract
+            .implementation_address
+            .or(self.implementation_address.clone());
+        contract.protocol = self.protocol.clone().or(contract.protocol);
+        contract.contract_type = self.contract_type.clone().or(contract.contract_type);
+        contract.version = self.version.clone().or(contract.version);
+        contract
+    }
+}
+
+/// Accept Postgres-style `t`/`f` as well as `true`/`false`, as exported by `psql`
+fn deserialize_pg_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.trim().to_lowercase().as_str() {
+        "t" | "true" => Ok(true),
+        "f" | "false" => Ok(false),
+        other => Err(serde::de::Error::custom(format!(
+            "invalid boolean {:?}, expected t/f or true/false",
+            other
+        ))),
     }
 }
 
@@ -51,7 +195,15 @@ impl From<ContractData> for ContractMetadata {
 pub struct EtherscanResponse {
     pub status: String,
     pub message: String,
-    pub result: Vec<EtherscanContract>,
+    pub result: EtherscanResult,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EtherscanResult {
+    Contracts(Vec<EtherscanContract>),
+    /// Error responses carry a plain string, e.g. "Max rate limit reached"
+    Message(String),
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,40 +228,224 @@ pub struct EtherscanContract {
 
 #[derive(Debug)]
 pub struct CuratedAddress {
-    pub address: String,
+    pub address: Address,
     pub chain_id: i32,
     pub protocol: Option<String>,
+    /// Known metadata carried into the fetched contract, e.g. from a metadata CSV
+    pub metadata: Option<ContractMetadata>,
 }
 
 impl CuratedAddress {
-    pub fn from_line(line: &str) -> Option<Self> {
-        // Skip comments and empty lines
-        let line = line.split('#').next()?.trim();
+    /// Parse an `address,chain_id,protocol` line. Comments and blank lines
+    /// give `Ok(None)`; anything else that isn't a valid entry is an error
+    /// describing why.
+    pub fn parse_line(line: &str) -> Result<Option<Self>, String> {
+        let line = line.split('#').next().unwrap_or("").trim();
         if line.is_empty() {
-            return None;
+            return Ok(None);
         }
 
-        // Parse: address,chain_id,protocol
         let parts: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
         if parts.len() < 2 {
-            return None;
+            return Err(format!(
+                "expected address,chain_id[,protocol], got {:?}",
+                line
+            ));
         }
+        if parts.len() > 3 {
+            return Err(format!("expected at most 3 fields, got {}", parts.len()));
+        }
+
+        let address = Address::parse(parts[0])?;
+        let chain_id = parts[1]
+            .parse()
+            .map_err(|_| format!("invalid chain id {:?}", parts[1]))?;
+        let protocol = parts
+            .get(2)
+            .filter(|protocol| !protocol.is_empty())
+            .map(|protocol| protocol.to_string());
 
-        Some(CuratedAddress {
-            address: parts[0].to_string(),
-            chain_id: parts[1].parse().ok()?,
-            protocol: parts.get(2).map(|s| s.to_string()),
-        })
+        Ok(Some(CuratedAddress {
+            address,
+            chain_id,
+            protocol,
+            metadata: None,
+        }))
     }
 }
 
-pub fn chain_id_to_name(chain_id: i32) -> String {
-    match chain_id {
-        1 => "ethereum".to_string(),
-        10 => "optimism".to_string(),
-        42161 => "arbitrum".to_string(),
-        8453 => "base".to_string(),
-        137 => "polygon".to_string(),
-        _ => format!("chain_{}", chain_id),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mixed-case vectors from EIP-55
+    const CHECKSUMMED: &[&str] = &[
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+        "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+        "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+        "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+    ];
+
+    #[test]
+    fn checksums_match_eip55_vectors() {
+        for &vector in CHECKSUMMED {
+            let address = Address::parse(vector).unwrap();
+            assert_eq!(address.as_str(), vector.to_lowercase());
+            assert_eq!(address.checksummed(), vector);
+        }
+    }
+
+    #[test]
+    fn accepts_single_case_without_checksum() {
+        let upper = Address::parse("0x52908400098527886E0F7030069857D2E4169EE7").unwrap();
+        assert_eq!(upper.as_str(), "0x52908400098527886e0f7030069857d2e4169ee7");
+        assert_eq!(
+            upper.checksummed(),
+            "0x52908400098527886E0F7030069857D2E4169EE7"
+        );
+
+        let lower = Address::parse("0xde709f2102306220921060314715629080e2fb77").unwrap();
+        assert_eq!(
+            lower.checksummed(),
+            "0xde709f2102306220921060314715629080e2fb77"
+        );
+
+        assert!(Address::parse("0X27B1FDB04752BBC536007A920D24ACB045561C26").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        // Last letter's case flipped
+        let err = Address::parse("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD").unwrap_err();
+        assert!(err.contains("EIP-55"), "{}", err);
+        assert!(err.contains(CHECKSUMMED[0]), "{}", err);
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!(Address::parse("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").is_err());
+        assert!(Address::parse("0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea").is_err());
+        assert!(Address::parse("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed00").is_err());
+        assert!(Address::parse("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaeg").is_err());
+        assert!(Address::parse("").is_err());
+    }
+
+    #[test]
+    fn from_str_trims_and_serde_round_trips() {
+        let address: Address = " 0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed "
+            .parse()
+            .unwrap();
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, "\"0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed\"");
+        assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+        assert!(
+            serde_json::from_str::<Address>("\"0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD\"")
+                .is_err()
+        );
+    }
+
+    fn address(hex_digit: char) -> Address {
+        Address::parse(&format!("0x{}", hex_digit.to_string().repeat(40))).unwrap()
+    }
+
+    fn fetched(implementation_address: Option<Address>) -> ContractData {
+        ContractData {
+            address: address('1'),
+            chain: "ethereum".to_string(),
+            chain_id: 1,
+            name: "TransparentUpgradeableProxy".to_string(),
+            symbol: None,
+            source_code: String::new(),
+            abi: "[]".to_string(),
+            is_proxy: implementation_address.is_some(),
+            implementation_address,
+            protocol: None,
+            contract_type: Some("Proxy".to_string()),
+            version: None,
+            updated_at: None,
+            compiler_version: None,
+            optimization_used: None,
+            optimization_runs: None,
+            evm_version: None,
+            constructor_arguments: None,
+            library: None,
+            license_type: None,
+            swarm_source: None,
+        }
+    }
+
+    fn curated(implementation_address: Option<Address>) -> ContractMetadata {
+        ContractMetadata {
+            address: address('1'),
+            chain: "ethereum".to_string(),
+            chain_id: 1,
+            name: "USDC".to_string(),
+            symbol: Some("USDC".to_string()),
+            is_proxy: true,
+            implementation_address,
+            protocol: Some("circle".to_string()),
+            contract_type: Some("Token".to_string()),
+            version: Some("v2".to_string()),
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn metadata_wins_except_for_the_fetched_implementation() {
+        let contract = curated(Some(address('2'))).apply_to(fetched(Some(address('3'))));
+        assert_eq!(contract.name, "USDC");
+        assert_eq!(contract.contract_type.as_deref(), Some("Token"));
+        assert_eq!(contract.version.as_deref(), Some("v2"));
+        assert_eq!(contract.implementation_address, Some(address('3')));
+
+        // The curated implementation only fills in for one Etherscan didn't report
+        let contract = curated(Some(address('2'))).apply_to(fetched(None));
+        assert!(contract.is_proxy);
+        assert_eq!(contract.implementation_address, Some(address('2')));
+    }
+
+    #[test]
+    fn curated_line_skips_comments_and_blanks() {
+        assert!(CuratedAddress::parse_line("").unwrap().is_none());
+        assert!(CuratedAddress::parse_line("   ").unwrap().is_none());
+        assert!(CuratedAddress::parse_line("# address,chain_id")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn curated_line_fields() {
+        let line = " 0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed , 10 , aave # v3 pool";
+        let curated = CuratedAddress::parse_line(line).unwrap().unwrap();
+        assert_eq!(
+            curated.address.as_str(),
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+        );
+        assert_eq!(curated.chain_id, 10);
+        assert_eq!(curated.protocol.as_deref(), Some("aave"));
+
+        // An empty protocol is no protocol
+        let curated = CuratedAddress::parse_line("0x1111111111111111111111111111111111111111,1,")
+            .unwrap()
+            .unwrap();
+        assert_eq!(curated.protocol, None);
+    }
+
+    #[test]
+    fn curated_line_rejects_bad_fields() {
+        let err =
+            CuratedAddress::parse_line("0x1111111111111111111111111111111111111111").unwrap_err();
+        assert!(err.contains("expected address,chain_id"), "{}", err);
+
+        let err = CuratedAddress::parse_line("0x1111111111111111111111111111111111111111,1,a,b")
+            .unwrap_err();
+        assert!(err.contains("at most 3 fields"), "{}", err);
+
+        let err = CuratedAddress::parse_line("0x1111111111111111111111111111111111111111,eth")
+            .unwrap_err();
+        assert!(err.contains("invalid chain id \"eth\""), "{}", err);
+
+        assert!(CuratedAddress::parse_line("0x1234,1").is_err());
     }
 }
@@ -0,0 +1,409 @@
+use crate::address_source::AddressSource;
+use crate::checkpoint::{self, contract_key, Checkpoint, ContractKey};
+use crate::contract_sink::ContractSink;
+use crate::etherscan::{EtherscanClient, FetchError};
+use crate::failures::FailureReport;
+use crate::models::{ContractData, CuratedAddress};
+use anyhow::Result;
+use futures::stream::{FuturesUnordered, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::{HashSet, VecDeque};
+use tokio::sync::mpsc;
+
+/// Fetched contracts buffered between the fetchers and the writer task
+const WRITER_CHANNEL_CAPACITY: usize = 64;
+
+/// Result of fetching one address, as seen by the writer task
+pub enum FetchOutcome {
+    Fetched(ContractData),
+    Failed {
+        target: CuratedAddress,
+        error: FetchError,
+    },
+}
+
+/// Where the writer task records per-address results besides the sinks
+struct RunLog {
+    checkpoint: Checkpoint,
+    failures: Option<FailureReport>,
+    failed: usize,
+}
+
+impl RunLog {
+    fn completed(&mut self, key: &ContractKey) -> Result<()> {
+        self.checkpoint.record_completed(key.0, &key.1)
+    }
+
+    fn failed(&mut self, target: &CuratedAddress, error: &FetchError) -> Result<()> {
+        self.failed += 1;
+        if let Some(failures) = &mut self.failures {
+            failures.record(target, error)?;
+        }
+        self.checkpoint
+            .record_failed(target.chain_id, target.address.as_str(), &error.to_string())
+    }
+}
+
+/// A sink with the contracts it has not written yet
+struct BufferedSink {
+    sink: Box<dyn ContractSink>,
+    buffer: Vec<ContractData>,
+    written: usize,
+}
+
+impl BufferedSink {
+    async fn flush(&mut self, pb: &ProgressBar) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let written = self.sink.write_batch(&self.buffer).await?;
+        if self.sink.batch_size() > 1 {
+            pb.println(format!(
+                "💾 Wrote batch of {} contracts to {}",
+                written,
+                self.sink.describe()
+            ));
+        }
+        self.written += written;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+/// Contracts written to one sink during a run
+pub struct SinkSummary {
+    pub description: String,
+    pub written: usize,
+}
+
+/// Outcome of a pipeline run
+pub struct RunSummary {
+    pub sinks: Vec<SinkSummary>,
+    /// Addresses that could not be fetched
+    pub failed: usize,
+    /// Where the failed addresses were reported, with `--failures-out`
+    pub failures_path: Option<String>,
+}
+
+/// Fetches addresses from any [`AddressSource`] into any set of [`ContractSink`]s
+pub struct FetchPipeline {
+    pub client: EtherscanClient,
+    pub concurrency: usize,
+    pub checkpoint: Checkpoint,
+    pub failures: Option<FailureReport>,
+    /// Skip addresses that are completed in the checkpoint or already present
+    /// in every sink
+    pub resume: bool,
+    /// Also fetch the implementations of proxies, up to this many hops
+    pub follow_proxies: Option<usize>,
+}
+
+impl FetchPipeline {
+    pub async fn run(
+        mut self,
+        source: &dyn AddressSource,
+        sinks: Vec<Box<dyn ContractSink>>,
+    ) -> Result<RunSummary> {
+        println!("📖 Reading {}", source.describe());
+        let mut addresses = source.load().await?;
+        println!("✓ Found {} addresses to fetch", addresses.len());
+
+        if self.resume {
+            let mut done = self.checkpoint.completed_keys();
+            done.extend(keys_in_every_sink(&sinks).await?);
+            addresses = checkpoint::skip_completed(addresses, &done);
+        }
+        self.checkpoint.set_pending(&addresses);
+        self.checkpoint.save()?;
+        let checkpoint_path = self.checkpoint.path().display().to_string();
+
+        let targets: Vec<String> = sinks.iter().map(|sink| sink.describe()).collect();
+        println!("💾 Writing to {}\n", targets.join(", "));
+
+        let log = RunLog {
+            checkpoint: self.checkpoint,
+            failures: self.failures,
+            failed: 0,
+        };
+        let pb = progress_bar(addresses.len());
+
+        let (tx, rx) = mpsc::channel(WRITER_CHANNEL_CAPACITY);
+        let writer = tokio::spawn(write_outcomes(sinks, log, pb.clone(), rx));
+
+        fetch_all(
+            &self.client,
+            addresses,
+            self.concurrency,
+            self.follow_proxies,
+            &pb,
+            tx,
+        )
+        .await;
+        let summary = writer.await??;
+
+        pb.finish_with_message("Done!");
+        println!("📍 Progress recorded in {}", checkpoint_path);
+
+        Ok(summary)
+    }
+}
+
+/// Keys present in all sinks; a contract missing from any sink must be refetched
+async fn keys_in_every_sink(sinks: &[Box<dyn ContractSink>]) -> Result<HashSet<ContractKey>> {
+    let mut common: Option<HashSet<ContractKey>> = None;
+
+    for sink in sinks {
+        let keys = sink.existing_keys().await?;
+        common = Some(match common {
+            Some(common) => common.intersection(&keys).cloned().collect(),
+            None => keys,
+        });
+    }
+
+    Ok(common.unwrap_or_default())
+}
+
+pub fn progress_bar(len: usize) -> ProgressBar {
+    let pb = ProgressBar::new(len as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("█▓▒░  "),
+    );
+    pb
+}
+
+/// Fetch `addresses` with up to `concurrency` requests in flight and send each
+/// outcome to `tx` as soon as it arrives.
+///
+/// With `follow_proxies` set, the implementation of every fetched proxy is
+/// queued as well, up to that many hops from a listed address, so
+/// proxy-of-proxy chains end at the logic contract. Beacon proxies are
+/// resolved through their beacon first (see [`resolve_beacon`]). Each
+/// contract is fetched at most once, which also cuts cycles.
+///
+/// The client's rate limiter still bounds the request rate; concurrency only
+/// hides request latency. Stops early if the receiving writer has gone away.
+async fn fetch_all(
+    client: &EtherscanClient,
+    addresses: Vec<CuratedAddress>,
+    concurrency: usize,
+    follow_proxies: Option<usize>,
+    pb: &ProgressBar,
+    tx: mpsc::Sender<FetchOutcome>,
+) {
+    let mut seen: HashSet<ContractKey> = addresses
+        .iter()
+        .map(|addr| contract_key(addr.chain_id, addr.address.as_str()))
+        .collect();
+    // Targets with their distance from a listed address
+    let mut queue: VecDeque<(CuratedAddress, usize)> =
+        addresses.into_iter().map(|addr| (addr, 0)).collect();
+    let mut in_flight = FuturesUnordered::new();
+
+    loop {
+        while in_flight.len() < concurrency.max(1) {
+            let Some((addr, depth)) = queue.pop_front() else {
+                break;
+            };
+            let follow = follow_proxies.is_some_and(|max_depth| depth < max_depth);
+            in_flight.push(async move {
+                pb.set_message(format!("Fetching {}", addr.address));
+                let result = match client
+                    .fetch_contract(&addr.address, addr.chain_id, addr.protocol.clone())
+                    .await
+                {
+                    Ok(contract) if follow && looks_like_proxy(&contract) => {
+                        Ok(resolve_beacon(client, contract, pb).await)
+                    }
+                    result => result,
+                };
+                (addr, depth, result)
+            });
+        }
+
+        let Some((addr, depth, result)) = in_flight.next().await else {
+            break;
+        };
+
+        let outcome = match result {
+            Ok(contract) => {
+                let contract = match &addr.metadata {
+                    Some(metadata) => metadata.apply_to(contract),
+                    None => contract,
+                };
+                pb.println(format!(
+                    "✓ {} - {}",
+                    contract.name,
+                    contract.address.checksummed()
+                ));
+
+                let follow = follow_proxies.is_some_and(|max_depth| depth < max_depth);
+                if let Some(implementation) =
+                    contract.implementation_address.clone().filter(|_| follow)
+                {
+                    if seen.insert(contract_key(contract.chain_id, implementation.as_str())) {
+                        pb.println(format!(
+                            "↪ Following proxy {} to {}",
+                            contract.address.checksummed(),
+                            implementation.checksummed()
+                        ));
+                        pb.inc_length(1);
+                        queue.push_back((
+                            CuratedAddress {
+                                address: implementation,
+                                chain_id: contract.chain_id,
+                                protocol: contract.protocol.clone(),
+                                metadata: None,
+                            },
+                            depth + 1,
+                        ));
+                    }
+                }
+
+                FetchOutcome::Fetched(contract)
+            }
+            Err(error) => {
+                pb.println(format!("✗ {} - Error: {}", addr.address, error));
+                FetchOutcome::Failed {
+                    target: addr,
+                    error,
+                }
+            }
+        };
+
+        pb.inc(1);
+
+        if tx.send(outcome).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Flagged as a proxy by Etherscan, or named like one (unflagged beacon proxies)
+fn looks_like_proxy(contract: &ContractData) -> bool {
+    contract.is_proxy || contract.contract_type.as_deref() == Some("Proxy")
+}
+
+/// Point a beacon proxy at the logic contract its EIP-1967 beacon currently
+/// returns. Etherscan reports either the beacon or nothing for these, which
+/// would leave the beacon's ABI in place of the logic contract's. A failed
+/// lookup keeps what Etherscan reported.
+async fn resolve_beacon(
+    client: &EtherscanClient,
+    mut contract: ContractData,
+    pb: &ProgressBar,
+) -> ContractData {
+    match client
+        .beacon_implementation(&contract.address, contract.chain_id)
+        .await
+    {
+        Ok(Some((beacon, logic))) => {
+            pb.println(format!(
+                "🔦 {} uses beacon {} → {}",
+                contract.address.checksummed(),
+                beacon.checksummed(),
+                logic.checksummed()
+            ));
+            contract.is_proxy = true;
+            contract.implementation_address = Some(logic);
+        }
+        Ok(None) => {}
+        Err(error) => pb.println(format!(
+            "⚠️  Could not check {} for a beacon: {}",
+            contract.address.checksummed(),
+            error
+        )),
+    }
+    contract
+}
+
+/// Writer task: hand contracts to every sink in its preferred batch size and
+/// mark each one completed once all sinks have written it
+async fn write_outcomes(
+    sinks: Vec<Box<dyn ContractSink>>,
+    mut log: RunLog,
+    pb: ProgressBar,
+    mut rx: mpsc::Receiver<FetchOutcome>,
+) -> Result<RunSummary> {
+    let mut sinks: Vec<BufferedSink> = sinks
+        .into_iter()
+        .map(|sink| BufferedSink {
+            sink,
+            buffer: Vec::new(),
+            written: 0,
+        })
+        .collect();
+    // Contracts received but not yet flushed by every sink, in arrival order
+    let mut unflushed: VecDeque<ContractKey> = VecDeque::new();
+
+    while let Some(outcome) = rx.recv().await {
+        match outcome {
+            FetchOutcome::Fetched(contract) => {
+                unflushed.push_back(contract_key(contract.chain_id, contract.address.as_str()));
+                for sink in &mut sinks {
+                    sink.buffer.push(contract.clone());
+                    if sink.buffer.len() >= sink.sink.batch_size().max(1) {
+                        sink.flush(&pb).await?;
+                    }
+                }
+            }
+            FetchOutcome::Failed { target, error } => log.failed(&target, &error)?,
+        }
+
+        record_flushed(&sinks, &mut unflushed, &mut log)?;
+    }
+
+    for sink in &mut sinks {
+        sink.flush(&pb).await?;
+        sink.sink.finish().await?;
+    }
+    record_flushed(&sinks, &mut unflushed, &mut log)?;
+    log.checkpoint.save()?;
+
+    let failures_path = match log.failures {
+        Some(report) => {
+            let path = report.path().to_string();
+            report.finish()?;
+            Some(path)
+        }
+        None => None,
+    };
+
+    Ok(RunSummary {
+        sinks: sinks
+            .into_iter()
+            .map(|sink| SinkSummary {
+                description: sink.sink.describe(),
+                written: sink.written,
+            })
+            .collect(),
+        failed: log.failed,
+        failures_path,
+    })
+}
+
+/// Mark as completed every contract that all sinks have flushed
+fn record_flushed(
+    sinks: &[BufferedSink],
+    unflushed: &mut VecDeque<ContractKey>,
+    log: &mut RunLog,
+) -> Result<()> {
+    // Every sink buffers the most recent contracts in arrival order, so the
+    // fullest buffer is the tail still waiting on at least one sink
+    let still_buffered = sinks
+        .iter()
+        .map(|sink| sink.buffer.len())
+        .max()
+        .unwrap_or(0);
+
+    while unflushed.len() > still_buffered {
+        if let Some(key) = unflushed.pop_front() {
+            log.completed(&key)?;
+        }
+    }
+
+    Ok(())
+}
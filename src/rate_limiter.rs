@@ -0,0 +1,67 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::time::sleep_until;
+
+/// Token-bucket rate limiter shared by every request made through a client.
+///
+/// Callers reserve a token up front and sleep until it becomes available, so
+/// waiting overlaps with the latency of requests already in flight instead of
+/// being added on top of it.
+pub struct RateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum tokens that can accumulate while idle
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    /// May go negative: each negative token is a caller already queued
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Limiter allowing `requests_per_second` with no burst above that rate.
+    ///
+    /// Etherscan counts requests per wall-clock second, so letting a bucket
+    /// fill up and then draining it would overshoot the quota; a capacity of
+    /// one token spaces requests evenly at `1 / requests_per_second`.
+    pub fn new(requests_per_second: f64) -> Self {
+        Self::with_capacity(requests_per_second, 1.0)
+    }
+
+    pub fn with_capacity(requests_per_second: f64, capacity: f64) -> Self {
+        assert!(requests_per_second > 0.0, "rate limit must be positive");
+
+        Self {
+            rate: requests_per_second,
+            capacity: capacity.max(1.0),
+            state: Mutex::new(BucketState {
+                tokens: capacity.max(1.0),
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until a request may be sent
+    pub async fn acquire(&self) {
+        let ready_at = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+
+            let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+            state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+            state.last_refill = now;
+
+            state.tokens -= 1.0;
+            if state.tokens >= 0.0 {
+                return;
+            }
+
+            now + Duration::from_secs_f64(-state.tokens / self.rate)
+        };
+
+        sleep_until(ready_at.into()).await;
+    }
+}
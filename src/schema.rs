@@ -0,0 +1,321 @@
+use crate::migrator::Migrator;
+use anyhow::{Context, Result};
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{ConnectOptions, Connection, Executor, PgConnection};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// One column as reported by `pg_attribute`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+}
+
+/// The parts of the `public` schema that migrations are responsible for
+#[derive(Debug, Default)]
+pub struct Schema {
+    /// Table name -> column name -> column
+    pub tables: BTreeMap<String, BTreeMap<String, Column>>,
+    /// Index name -> `CREATE INDEX` definition
+    pub indexes: BTreeMap<String, String>,
+    /// `table.constraint` -> constraint definition
+    pub constraints: BTreeMap<String, String>,
+}
+
+impl Schema {
+    pub async fn introspect(conn: &mut PgConnection) -> Result<Self> {
+        let mut schema = Schema::default();
+
+        let tables: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT table_name::TEXT
+            FROM information_schema.tables
+            WHERE table_schema = 'public' AND table_type = 'BASE TABLE'
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        for (table,) in tables {
+            schema.tables.insert(table, BTreeMap::new());
+        }
+
+        let columns: Vec<(String, String, String, bool, Option<String>)> = sqlx::query_as(
+            r#"
+            SELECT c.relname::TEXT, a.attname::TEXT, format_type(a.atttypid, a.atttypmod),
+                   NOT a.attnotnull, pg_get_expr(d.adbin, d.adrelid)
+            FROM pg_attribute a
+            JOIN pg_class c ON c.oid = a.attrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+            WHERE n.nspname = 'public' AND c.relkind IN ('r', 'p')
+              AND a.attnum > 0 AND NOT a.attisdropped
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        for (table, column, data_type, nullable, default) in columns {
+            schema.tables.entry(table).or_default().insert(
+                column,
+                Column {
+                    data_type,
+                    nullable,
+                    default,
+                },
+            );
+        }
+
+        let indexes: Vec<(String, String)> = sqlx::query_as(
+            "SELECT indexname::TEXT, indexdef FROM pg_indexes WHERE schemaname = 'public'",
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        schema.indexes.extend(indexes);
+
+        let constraints: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT c.relname || '.' || con.conname, pg_get_constraintdef(con.oid)
+            FROM pg_constraint con
+            JOIN pg_class c ON c.oid = con.conrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = 'public'
+            "#,
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        schema.constraints.extend(constraints);
+
+        Ok(schema)
+    }
+}
+
+/// A difference between the live schema and the one the migrations produce
+pub enum Drift {
+    /// In the migrations but not in the database
+    Missing { kind: &'static str, name: String },
+    /// In the database but not in the migrations
+    Extra { kind: &'static str, name: String },
+    Changed {
+        kind: &'static str,
+        name: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Drift::Missing { kind, name } => write!(f, "➖ missing {}: {}", kind, name),
+            Drift::Extra { kind, name } => write!(f, "➕ extra {}: {}", kind, name),
+            Drift::Changed {
+                kind,
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "✏️  {} {} differs\n      expected: {}\n      actual:   {}",
+                kind, name, expected, actual
+            ),
+        }
+    }
+}
+
+/// Compare the `actual` live schema against the `expected` one
+pub fn diff(expected: &Schema, actual: &Schema) -> Vec<Drift> {
+    let mut drift = Vec::new();
+
+    for (table, expected_columns) in &expected.tables {
+        let Some(actual_columns) = actual.tables.get(table) else {
+            drift.push(Drift::Missing {
+                kind: "table",
+                name: table.clone(),
+            });
+            continue;
+        };
+
+        for (column, expected_column) in expected_columns {
+            let name = format!("{}.{}", table, column);
+            match actual_columns.get(column) {
+                None => drift.push(Drift::Missing {
+                    kind: "column",
+                    name,
+                }),
+                Some(actual_column) => {
+                    drift.extend(diff_column(&name, expected_column, actual_column))
+                }
+            }
+        }
+        for column in actual_columns.keys() {
+            if !expected_columns.contains_key(column) {
+                drift.push(Drift::Extra {
+                    kind: "column",
+                    name: format!("{}.{}", table, column),
+                });
+            }
+        }
+    }
+    for table in actual.tables.keys() {
+        if !expected.tables.contains_key(table) {
+            drift.push(Drift::Extra {
+                kind: "table",
+                name: table.clone(),
+            });
+        }
+    }
+
+    drift.extend(diff_definitions(
+        "index",
+        &expected.indexes,
+        &actual.indexes,
+    ));
+    drift.extend(diff_definitions(
+        "constraint",
+        &expected.constraints,
+        &actual.constraints,
+    ));
+
+    drift
+}
+
+fn diff_column(name: &str, expected: &Column, actual: &Column) -> Vec<Drift> {
+    let mut drift = Vec::new();
+    let mut changed = |kind: &'static str, expected: String, actual: String| {
+        if expected != actual {
+            drift.push(Drift::Changed {
+                kind,
+                name: name.to_string(),
+                expected,
+                actual,
+            });
+        }
+    };
+
+    changed(
+        "column type of",
+        expected.data_type.clone(),
+        actual.data_type.clone(),
+    );
+    changed(
+        "nullability of",
+        nullability(expected.nullable),
+        nullability(actual.nullable),
+    );
+    changed(
+        "default of",
+        expected
+            .default
+            .clone()
+            .unwrap_or_else(|| "none".to_string()),
+        actual.default.clone().unwrap_or_else(|| "none".to_string()),
+    );
+
+    drift
+}
+
+fn nullability(nullable: bool) -> String {
+    if nullable { "NULL" } else { "NOT NULL" }.to_string()
+}
+
+fn diff_definitions(
+    kind: &'static str,
+    expected: &BTreeMap<String, String>,
+    actual: &BTreeMap<String, String>,
+) -> Vec<Drift> {
+    let mut drift = Vec::new();
+
+    for (name, definition) in expected {
+        match actual.get(name) {
+            None => drift.push(Drift::Missing {
+                kind,
+                name: name.clone(),
+            }),
+            Some(actual_definition) if actual_definition != definition => {
+                drift.push(Drift::Changed {
+                    kind,
+                    name: name.clone(),
+                    expected: definition.clone(),
+                    actual: actual_definition.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for name in actual.keys() {
+        if !expected.contains_key(name) {
+            drift.push(Drift::Extra {
+                kind,
+                name: name.clone(),
+            });
+        }
+    }
+
+    drift
+}
+
+/// Build the schema the migrations produce. With no `scratch_url`, a throwaway
+/// database is created next to `database_url` and dropped afterwards, even if
+/// applying the migrations fails.
+pub async fn expected_schema(
+    migrator: &Migrator,
+    database_url: &str,
+    scratch_url: Option<&str>,
+) -> Result<Schema> {
+    if let Some(scratch_url) = scratch_url {
+        let mut conn = PgConnection::connect(scratch_url)
+            .await
+            .context("Failed to connect to the scratch database")?;
+        let schema = build_from_migrations(migrator, &mut conn).await;
+        let closed = conn.close().await;
+        let schema = schema?;
+        closed?;
+        return Ok(schema);
+    }
+
+    let options = PgConnectOptions::from_str(database_url).context("Invalid database URL")?;
+    let scratch_name = format!("schema_diff_{}", std::process::id());
+
+    let mut admin = options.connect().await?;
+    admin
+        .execute(format!("CREATE DATABASE {}", scratch_name).as_str())
+        .await
+        .context("Failed to create a scratch database (use --scratch-url instead)")?;
+
+    let schema = match options.clone().database(&scratch_name).connect().await {
+        Ok(mut conn) => {
+            let schema = build_from_migrations(migrator, &mut conn).await;
+            // The scratch database can only be dropped once this connection is gone
+            let closed = conn.close().await.map_err(anyhow::Error::from);
+            schema.and_then(|schema| closed.map(|()| schema))
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    let dropped = admin
+        .execute(format!("DROP DATABASE IF EXISTS {}", scratch_name).as_str())
+        .await
+        .with_context(|| format!("Failed to drop scratch database {}", scratch_name));
+    admin.close().await?;
+
+    match (schema, dropped) {
+        (Ok(schema), Ok(_)) => Ok(schema),
+        (Ok(_), Err(e)) => Err(e),
+        (Err(e), dropped) => {
+            if let Err(drop_error) = dropped {
+                eprintln!("⚠️  {:#}", drop_error);
+            }
+            Err(e)
+        }
+    }
+}
+
+async fn build_from_migrations(migrator: &Migrator, conn: &mut PgConnection) -> Result<Schema> {
+    migrator
+        .run(conn, false)
+        .await
+        .context("Failed to apply migrations to the scratch database")?;
+    Schema::introspect(conn).await
+}
@@ -0,0 +1,206 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// How Etherscan returned a contract's `SourceCode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    /// One flattened file
+    SingleFile,
+    /// A JSON object of `{path: {content}}`
+    MultiFile,
+    /// Solidity standard JSON input, wrapped in an extra pair of braces
+    StandardJson,
+}
+
+impl SourceFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceFormat::SingleFile => "single_file",
+            SourceFormat::MultiFile => "multi_file",
+            SourceFormat::StandardJson => "standard_json",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// A contract's source split into files
+#[derive(Debug, Clone)]
+pub struct ContractSources {
+    pub format: SourceFormat,
+    pub files: Vec<SourceFile>,
+    /// The `settings` of a standard JSON input (optimizer, remappings, ...)
+    pub settings: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct FileEntry {
+    content: String,
+}
+
+#[derive(Deserialize)]
+struct StandardJsonInput {
+    sources: BTreeMap<String, FileEntry>,
+    #[serde(default)]
+    settings: Option<Value>,
+}
+
+/// Split `source_code` as returned by Etherscan into files. Anything that
+/// isn't valid JSON is a single file named after the contract. Returns `None`
+/// for an empty source.
+pub fn parse_sources(
+    source_code: &str,
+    contract_name: &str,
+    compiler_version: Option<&str>,
+) -> Option<ContractSources> {
+    let trimmed = source_code.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    // Etherscan wraps standard JSON input in `{{ ... }}`
+    if let Some(inner) = trimmed
+        .strip_prefix("{{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+    {
+        if let Ok(input) = serde_json::from_str::<StandardJsonInput>(&format!("{{{}}}", inner)) {
+            return Some(standard_json(input));
+        }
+    }
+
+    if trimmed.starts_with('{') {
+        if let Ok(input) = serde_json::from_str::<StandardJsonInput>(trimmed) {
+            return Some(standard_json(input));
+        }
+        if let Ok(files) = serde_json::from_str::<BTreeMap<String, FileEntry>>(trimmed) {
+            return Some(ContractSources {
+                format: SourceFormat::MultiFile,
+                files: into_files(files),
+                settings: None,
+            });
+        }
+    }
+
+    let extension = match compiler_version {
+        Some(version) if version.to_lowercase().starts_with("vyper") => "vy",
+        _ => "sol",
+    };
+    Some(ContractSources {
+        format: SourceFormat::SingleFile,
+        files: vec![SourceFile {
+            path: format!("{}.{}", contract_name, extension),
+            content: source_code.to_string(),
+        }],
+        settings: None,
+    })
+}
+
+fn standard_json(input: StandardJsonInput) -> ContractSources {
+    ContractSources {
+        format: SourceFormat::StandardJson,
+        files: into_files(input.sources),
+        settings: input.settings,
+    }
+}
+
+fn into_files(files: BTreeMap<String, FileEntry>) -> Vec<SourceFile> {
+    files
+        .into_iter()
+        .map(|(path, entry)| SourceFile {
+            path,
+            content: entry.content,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(sources: &ContractSources) -> Vec<&str> {
+        sources
+            .files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn empty_source_is_none() {
+        assert!(parse_sources("", "Token", None).is_none());
+        assert!(parse_sources("  \n", "Token", None).is_none());
+    }
+
+    #[test]
+    fn flattened_solidity_is_one_file() {
+        let code = "pragma solidity ^0.8.0;\ncontract Token {}";
+        let sources = parse_sources(code, "Token", Some("v0.8.20+commit.a1b79de6")).unwrap();
+        assert_eq!(sources.format, SourceFormat::SingleFile);
+        assert_eq!(paths(&sources), ["Token.sol"]);
+        assert_eq!(sources.files[0].content, code);
+        assert!(sources.settings.is_none());
+    }
+
+    #[test]
+    fn vyper_gets_vy_extension() {
+        let sources = parse_sources("# @version 0.3.7\n", "Pool", Some("vyper:0.3.7")).unwrap();
+        assert_eq!(sources.format, SourceFormat::SingleFile);
+        assert_eq!(paths(&sources), ["Pool.vy"]);
+    }
+
+    #[test]
+    fn brace_that_is_not_json_is_one_file() {
+        let sources = parse_sources("{ not json", "Odd", None).unwrap();
+        assert_eq!(sources.format, SourceFormat::SingleFile);
+        assert_eq!(paths(&sources), ["Odd.sol"]);
+    }
+
+    #[test]
+    fn json_object_of_files_is_multi_file() {
+        let code = r#"{
+            "contracts/Token.sol": {"content": "import './Lib.sol';"},
+            "contracts/Lib.sol": {"content": "library Lib {}"}
+        }"#;
+        let sources = parse_sources(code, "Token", None).unwrap();
+        assert_eq!(sources.format, SourceFormat::MultiFile);
+        assert_eq!(
+            paths(&sources),
+            ["contracts/Lib.sol", "contracts/Token.sol"]
+        );
+        assert_eq!(sources.files[0].content, "library Lib {}");
+        assert!(sources.settings.is_none());
+    }
+
+    #[test]
+    fn double_braced_standard_json_keeps_settings() {
+        let code = r#"{{
+            "language": "Solidity",
+            "sources": {
+                "src/Vault.sol": {"content": "contract Vault {}"},
+                "@oz/Ownable.sol": {"content": "abstract contract Ownable {}"}
+            },
+            "settings": {"optimizer": {"enabled": true, "runs": 200}, "evmVersion": "paris"}
+        }}"#;
+        let sources = parse_sources(code, "Vault", None).unwrap();
+        assert_eq!(sources.format, SourceFormat::StandardJson);
+        assert_eq!(paths(&sources), ["@oz/Ownable.sol", "src/Vault.sol"]);
+
+        let settings = sources.settings.unwrap();
+        assert_eq!(settings["optimizer"]["runs"], 200);
+        assert_eq!(settings["evmVersion"], "paris");
+    }
+
+    #[test]
+    fn single_braced_standard_json_is_recognized() {
+        let code = r#"{"language": "Vyper", "sources": {"Pool.vy": {"content": "x: uint256"}}}"#;
+        let sources = parse_sources(code, "Pool", Some("vyper:0.3.10")).unwrap();
+        assert_eq!(sources.format, SourceFormat::StandardJson);
+        assert_eq!(paths(&sources), ["Pool.vy"]);
+        assert!(sources.settings.is_none());
+    }
+}
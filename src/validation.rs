@@ -0,0 +1,281 @@
+use crate::chains;
+use crate::checkpoint::{contract_key, ContractKey};
+use crate::models::{Address, ContractMetadata, CuratedAddress};
+use anyhow::{bail, Context, Result};
+use csv::Reader;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+/// An input line that was rejected, and why
+#[derive(Debug)]
+pub struct Rejection {
+    pub line: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+/// The accepted rows of an input file, plus every rejected line
+#[derive(Debug)]
+pub struct Validated<T> {
+    pub rows: Vec<T>,
+    pub rejected: Vec<Rejection>,
+}
+
+impl<T> Validated<T> {
+    /// The accepted rows, or an error listing every rejected line of `path`
+    pub fn into_rows(self, path: &str) -> Result<Vec<T>> {
+        if !self.rejected.is_empty() {
+            let lines: Vec<String> = self.rejected.iter().map(ToString::to_string).collect();
+            bail!(
+                "{} invalid lines in {} (see `validate-input`):\n  {}",
+                self.rejected.len(),
+                path,
+                lines.join("\n  ")
+            );
+        }
+        Ok(self.rows)
+    }
+}
+
+/// Reject `(chain_id, address)` pairs already seen on an earlier line
+#[derive(Default)]
+struct DuplicateCheck {
+    seen: HashMap<ContractKey, u64>,
+}
+
+impl DuplicateCheck {
+    fn check(&mut self, line: u64, chain_id: i32, address: &str) -> Result<(), String> {
+        let key = contract_key(chain_id, address);
+        if let Some(first) = self.seen.get(&key) {
+            return Err(format!(
+                "duplicate of line {} ({} on chain {})",
+                first, address, chain_id
+            ));
+        }
+        self.seen.insert(key, line);
+        Ok(())
+    }
+}
+
+/// Reject chain ids missing from the chain registry
+fn check_chain(chain_id: i32) -> Result<(), String> {
+    match chains::registry().get(chain_id) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "unknown chain id {} (see `chains list`; add it with --chains)",
+            chain_id
+        )),
+    }
+}
+
+/// `targets` if every chain id is in the registry, otherwise an error naming
+/// each address on an unknown chain; for sources without line numbers
+pub fn known_chains(targets: Vec<CuratedAddress>, source: &str) -> Result<Vec<CuratedAddress>> {
+    let unknown: Vec<String> = targets
+        .iter()
+        .filter_map(|target| {
+            check_chain(target.chain_id)
+                .err()
+                .map(|reason| format!("{}: {}", target.address, reason))
+        })
+        .collect();
+    if !unknown.is_empty() {
+        bail!(
+            "{} addresses in {} are on unknown chains:\n  {}",
+            unknown.len(),
+            source,
+            unknown.join("\n  ")
+        );
+    }
+    Ok(targets)
+}
+
+/// Parse a curated `address,chain_id,protocol` file strictly
+pub fn curated_file(path: &str) -> Result<Validated<CuratedAddress>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let mut duplicates = DuplicateCheck::default();
+    let mut validated = Validated {
+        rows: Vec::new(),
+        rejected: Vec::new(),
+    };
+
+    for (index, line) in content.lines().enumerate() {
+        let line_number = index as u64 + 1;
+        let parsed = CuratedAddress::parse_line(line).and_then(|parsed| match parsed {
+            Some(addr) => check_chain(addr.chain_id)
+                .and_then(|_| duplicates.check(line_number, addr.chain_id, addr.address.as_str()))
+                .map(|_| Some(addr)),
+            None => Ok(None),
+        });
+
+        match parsed {
+            Ok(Some(addr)) => validated.rows.push(addr),
+            Ok(None) => {}
+            Err(reason) => validated.rejected.push(Rejection {
+                line: line_number,
+                reason,
+            }),
+        }
+    }
+
+    Ok(validated)
+}
+
+/// Read a metadata CSV such as `contracts-metadata.csv` by header name
+pub fn metadata_csv(path: &str) -> Result<Validated<ContractMetadata>> {
+    let mut reader = Reader::from_path(path).with_context(|| format!("Failed to open {}", path))?;
+    let headers = reader.headers()?.clone();
+    let mut duplicates = DuplicateCheck::default();
+    let mut validated = Validated {
+        rows: Vec::new(),
+        rejected: Vec::new(),
+    };
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                validated.rejected.push(Rejection {
+                    line: e.position().map_or(0, |pos| pos.line()),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |pos| pos.line());
+
+        let parsed = record
+            .deserialize::<ContractMetadata>(Some(&headers))
+            .map_err(|e| deserialize_error(&e))
+            .and_then(|row| {
+                check_chain(row.chain_id)?;
+                duplicates.check(line, row.chain_id, row.address.as_str())?;
+                Ok(row)
+            });
+
+        match parsed {
+            Ok(row) => validated.rows.push(row),
+            Err(reason) => validated.rejected.push(Rejection { line, reason }),
+        }
+    }
+
+    Ok(validated)
+}
+
+/// Check the address columns of a contracts CSV, which `seed` copies into
+/// the database without parsing its rows
+pub fn contracts_csv_addresses(path: &str) -> Result<Validated<()>> {
+    let mut reader = Reader::from_path(path).with_context(|| format!("Failed to open {}", path))?;
+    let headers = reader.headers()?.clone();
+    let columns: Vec<(usize, &str)> = ["address", "implementation_address"]
+        .into_iter()
+        .filter_map(|name| headers.iter().position(|h| h == name).map(|i| (i, name)))
+        .collect();
+    let mut validated = Validated {
+        rows: Vec::new(),
+        rejected: Vec::new(),
+    };
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                validated.rejected.push(Rejection {
+                    line: e.position().map_or(0, |pos| pos.line()),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |pos| pos.line());
+
+        for &(index, column) in &columns {
+            let value = record.get(index).unwrap_or("").trim();
+            if value.is_empty() && column == "implementation_address" {
+                continue;
+            }
+            if let Err(reason) = Address::parse(value) {
+                validated.rejected.push(Rejection {
+                    line,
+                    reason: format!("{}: {}", column, reason),
+                });
+            }
+        }
+    }
+
+    Ok(validated)
+}
+
+/// The field-level message of a deserialize error, without csv's position prefix
+fn deserialize_error(error: &csv::Error) -> String {
+    match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => error.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(content: &str) -> Validated<CuratedAddress> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("addresses.txt");
+        fs::write(&path, content).unwrap();
+        curated_file(path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn curated_file_accepts_valid_lines() {
+        let validated = validate(
+            "# address,chain_id,protocol\n\
+             \n\
+             0x1111111111111111111111111111111111111111,1,uniswap\n\
+             0x1111111111111111111111111111111111111111,10\n",
+        );
+        assert!(validated.rejected.is_empty(), "{:?}", validated.rejected);
+        assert_eq!(validated.rows.len(), 2);
+    }
+
+    #[test]
+    fn curated_file_rejects_with_line_numbers() {
+        let validated = validate(
+            "# address,chain_id,protocol\n\
+             0x1111111111111111111111111111111111111111,1\n\
+             0x2222222222222222222222222222222222222222,999999\n\
+             0x1111111111111111111111111111111111111111,1,aave\n\
+             0x2222222222222222222222222222222222222222\n\
+             0x3333333333333333333333333333333333333333,1\n",
+        );
+        let rejected: Vec<(u64, &str)> = validated
+            .rejected
+            .iter()
+            .map(|rejection| (rejection.line, rejection.reason.as_str()))
+            .collect();
+        assert_eq!(rejected.len(), 3, "{:?}", rejected);
+        assert_eq!(rejected[0].0, 3);
+        assert!(rejected[0].1.contains("unknown chain id 999999"));
+        assert_eq!(rejected[1].0, 4);
+        assert!(rejected[1].1.contains("duplicate of line 2"));
+        assert_eq!(rejected[2].0, 5);
+        assert!(rejected[2].1.contains("expected address,chain_id"));
+        assert_eq!(validated.rows.len(), 2);
+    }
+
+    #[test]
+    fn duplicates_ignore_address_case() {
+        let validated = validate(
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed,1\n\
+             0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed,1\n",
+        );
+        assert_eq!(validated.rejected.len(), 1);
+        assert_eq!(validated.rejected[0].line, 2);
+        assert!(validated.rejected[0].reason.contains("duplicate of line 1"));
+    }
+}